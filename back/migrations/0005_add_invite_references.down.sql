@@ -0,0 +1,4 @@
+ALTER TABLE accounts DROP FOREIGN KEY accounts_invite_id_fk;
+ALTER TABLE accounts DROP COLUMN invite_id;
+ALTER TABLE invites DROP FOREIGN KEY invites_invited_by_fk;
+ALTER TABLE invites DROP COLUMN invited_by;
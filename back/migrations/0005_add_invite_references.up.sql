@@ -0,0 +1,6 @@
+ALTER TABLE invites
+    ADD COLUMN invited_by BIGINT UNSIGNED NULL,
+    ADD CONSTRAINT invites_invited_by_fk FOREIGN KEY (invited_by) REFERENCES accounts (id) ON DELETE SET NULL ON UPDATE CASCADE;
+ALTER TABLE accounts
+    ADD COLUMN invite_id BIGINT UNSIGNED NULL,
+    ADD CONSTRAINT accounts_invite_id_fk FOREIGN KEY (invite_id) REFERENCES invites (id) ON DELETE SET NULL ON UPDATE CASCADE;
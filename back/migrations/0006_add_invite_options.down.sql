@@ -0,0 +1,4 @@
+ALTER TABLE invites
+    DROP COLUMN email,
+    DROP COLUMN max_uses,
+    DROP COLUMN uses;
@@ -0,0 +1,5 @@
+ALTER TABLE invites
+    ADD COLUMN email VARCHAR(255) NULL,
+    ADD COLUMN max_uses INT UNSIGNED NOT NULL DEFAULT 1,
+    ADD COLUMN uses INT UNSIGNED NOT NULL DEFAULT 0;
+UPDATE invites SET uses = (SELECT COUNT(*) FROM accounts WHERE accounts.invite_id = invites.id);
@@ -0,0 +1,7 @@
+ALTER TABLE sessions
+    DROP COLUMN id,
+    DROP INDEX sessions_token_unique,
+    ADD PRIMARY KEY (token),
+    DROP COLUMN last_seen_at,
+    DROP COLUMN ip,
+    DROP COLUMN user_agent;
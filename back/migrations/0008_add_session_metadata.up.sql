@@ -0,0 +1,7 @@
+ALTER TABLE sessions
+    DROP PRIMARY KEY,
+    ADD COLUMN id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY FIRST,
+    ADD CONSTRAINT sessions_token_unique UNIQUE (token),
+    ADD COLUMN last_seen_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN ip VARCHAR(45) NULL,
+    ADD COLUMN user_agent VARCHAR(255) NULL;
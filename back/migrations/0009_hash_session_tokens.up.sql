@@ -0,0 +1,2 @@
+-- Session tokens are now stored as HMAC-SHA256 hashes, invalidate plaintext ones
+DELETE FROM sessions;
@@ -0,0 +1,7 @@
+DROP TABLE settings;
+DROP TABLE signin_challenges;
+DROP TABLE recovery_codes;
+ALTER TABLE accounts
+    DROP COLUMN totp_secret,
+    DROP COLUMN totp_enabled_at,
+    DROP COLUMN totp_last_step;
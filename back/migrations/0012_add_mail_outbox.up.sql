@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS mail_outbox (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    recipient VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    body TEXT NOT NULL,
+    attempts INT UNSIGNED NOT NULL DEFAULT 0,
+    last_error TEXT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    next_attempt_at TIMESTAMP NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX mail_outbox_next_attempt_at_index (next_attempt_at)
+);
@@ -0,0 +1,12 @@
+ALTER TABLE accounts ADD COLUMN email_verified_at TIMESTAMP NULL;
+-- Existing accounts signed up with invites that were handed out personally
+UPDATE accounts SET email_verified_at = created_at;
+CREATE TABLE IF NOT EXISTS email_verifications (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    account_id BIGINT UNSIGNED NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES accounts (id) ON DELETE CASCADE ON UPDATE CASCADE
+);
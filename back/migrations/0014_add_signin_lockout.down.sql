@@ -0,0 +1,4 @@
+DROP TABLE signin_failures;
+ALTER TABLE settings
+    DROP COLUMN signin_lockout_threshold,
+    DROP COLUMN signin_generic_errors;
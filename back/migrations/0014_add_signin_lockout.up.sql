@@ -0,0 +1,12 @@
+ALTER TABLE settings
+    ADD COLUMN signin_lockout_threshold INT UNSIGNED NOT NULL DEFAULT 5,
+    ADD COLUMN signin_generic_errors BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE TABLE IF NOT EXISTS signin_failures (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    account_id BIGINT UNSIGNED NULL,
+    ip VARCHAR(45) NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    INDEX signin_failures_ip_index (ip),
+    FOREIGN KEY (account_id) REFERENCES accounts (id) ON DELETE CASCADE ON UPDATE CASCADE
+);
@@ -0,0 +1,2 @@
+ALTER TABLE sessions ADD COLUMN reauthenticated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+UPDATE sessions SET reauthenticated_at = created_at;
@@ -0,0 +1,2 @@
+DROP TABLE magic_links;
+ALTER TABLE accounts DROP COLUMN magic_link_enabled;
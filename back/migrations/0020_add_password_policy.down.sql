@@ -0,0 +1,4 @@
+ALTER TABLE settings
+    DROP COLUMN password_min_length,
+    DROP COLUMN password_max_length,
+    DROP COLUMN password_disallow_email;
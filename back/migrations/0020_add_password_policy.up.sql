@@ -0,0 +1,4 @@
+ALTER TABLE settings
+    ADD COLUMN password_min_length INT UNSIGNED NOT NULL DEFAULT 8,
+    ADD COLUMN password_max_length INT UNSIGNED NOT NULL DEFAULT 128,
+    ADD COLUMN password_disallow_email BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    -- No foreign keys, entries outlive the accounts they mention
+    actor_id BIGINT UNSIGNED NULL,
+    action VARCHAR(64) NOT NULL,
+    target_id BIGINT UNSIGNED NULL,
+    details TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX audit_log_target_id_index (target_id)
+);
@@ -0,0 +1,26 @@
+CREATE TABLE IF NOT EXISTS role_permissions (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    role ENUM('none', 'editor', 'admin', 'superadmin') DEFAULT 'none', -- HACK: https://github.com/launchbadge/sqlx/issues/3750
+    permission VARCHAR(64) NOT NULL,
+    UNIQUE (role, permission)
+);
+-- Superadmins have every permission without being listed
+INSERT INTO role_permissions (role, permission) VALUES
+    ('editor', 'donations.read'),
+    ('editor', 'donations.write'),
+    ('editor', 'donations.delete'),
+    ('editor', 'supporters.read'),
+    ('editor', 'supporters.write'),
+    ('editor', 'supporters.delete'),
+    ('admin', 'donations.read'),
+    ('admin', 'donations.write'),
+    ('admin', 'donations.delete'),
+    ('admin', 'supporters.read'),
+    ('admin', 'supporters.write'),
+    ('admin', 'supporters.delete'),
+    ('admin', 'users.read'),
+    ('admin', 'users.role'),
+    ('admin', 'users.delete'),
+    ('admin', 'users.unlock'),
+    ('admin', 'settings.read'),
+    ('admin', 'permissions.read');
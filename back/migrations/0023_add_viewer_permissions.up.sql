@@ -0,0 +1,3 @@
+INSERT IGNORE INTO role_permissions (role, permission) VALUES
+    ('none', 'donations.read'),
+    ('none', 'supporters.read');
@@ -0,0 +1,2 @@
+ALTER TABLE invites DROP COLUMN co_op;
+ALTER TABLE accounts DROP COLUMN co_op;
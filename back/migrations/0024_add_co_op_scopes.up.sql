@@ -0,0 +1,3 @@
+-- Limits donations and supporters access to one co-op, all co-ops if NULL
+ALTER TABLE accounts ADD COLUMN co_op ENUM('S4L', 'STUDIO_MATIC') NULL;
+ALTER TABLE invites ADD COLUMN co_op ENUM('S4L', 'STUDIO_MATIC') NULL;
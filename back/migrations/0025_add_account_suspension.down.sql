@@ -0,0 +1,6 @@
+DELETE FROM role_permissions WHERE permission = 'users.suspend';
+INSERT IGNORE INTO role_permissions (role, permission) VALUES ('admin', 'users.delete');
+ALTER TABLE accounts
+    DROP COLUMN suspended_at,
+    DROP COLUMN suspension_reason,
+    DROP COLUMN deleted_at;
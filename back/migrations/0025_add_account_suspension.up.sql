@@ -0,0 +1,7 @@
+ALTER TABLE accounts
+    ADD COLUMN suspended_at TIMESTAMP NULL,
+    ADD COLUMN suspension_reason VARCHAR(1023) NULL,
+    ADD COLUMN deleted_at TIMESTAMP NULL;
+-- Deleting accounts is reserved to superadmins
+DELETE FROM role_permissions WHERE permission = 'users.delete';
+INSERT IGNORE INTO role_permissions (role, permission) VALUES ('admin', 'users.suspend');
@@ -0,0 +1,5 @@
+DELETE FROM sessions WHERE impersonator_id IS NOT NULL;
+ALTER TABLE sessions
+    DROP FOREIGN KEY sessions_impersonator_id_fk;
+ALTER TABLE sessions
+    DROP COLUMN impersonator_id;
@@ -0,0 +1,3 @@
+ALTER TABLE sessions
+    ADD COLUMN impersonator_id BIGINT UNSIGNED NULL,
+    ADD CONSTRAINT sessions_impersonator_id_fk FOREIGN KEY (impersonator_id) REFERENCES accounts (id) ON DELETE CASCADE ON UPDATE CASCADE;
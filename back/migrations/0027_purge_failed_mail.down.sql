@@ -0,0 +1,2 @@
+-- Deleted mails cannot be restored
+DO 0;
@@ -0,0 +1,2 @@
+-- Mails that ran out of attempts are deleted instead of kept without a next attempt
+DELETE FROM mail_outbox WHERE next_attempt_at IS NULL;
@@ -0,0 +1,11 @@
+-- Failures are short-lived, recreating the table is simpler than migrating them
+DROP TABLE signin_failures;
+CREATE TABLE signin_failures (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    email VARCHAR(255) NULL,
+    ip VARCHAR(45) NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    INDEX signin_failures_email_index (email),
+    INDEX signin_failures_ip_index (ip)
+);
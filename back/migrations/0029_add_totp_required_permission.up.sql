@@ -0,0 +1,2 @@
+-- Admins had to enroll a second factor by their role before
+INSERT INTO role_permissions (role, permission) VALUES ('admin', 'totp.required');
@@ -0,0 +1 @@
+ALTER TABLE settings RENAME COLUMN require_totp TO require_totp_for_admins;
@@ -0,0 +1,2 @@
+-- Which roles need a second factor is the `totp.required` permission since 0029
+ALTER TABLE settings RENAME COLUMN require_totp_for_admins TO require_totp;
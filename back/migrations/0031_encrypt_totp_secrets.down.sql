@@ -0,0 +1,6 @@
+-- Encrypted secrets cannot be decrypted here, disable two-factor authentication using them
+UPDATE accounts
+    SET totp_enabled_at = NULL, totp_last_step = NULL
+    WHERE totp_secret IS NOT NULL AND totp_secret_plaintext IS NULL;
+ALTER TABLE accounts DROP COLUMN totp_secret;
+ALTER TABLE accounts RENAME COLUMN totp_secret_plaintext TO totp_secret;
@@ -0,0 +1,3 @@
+-- Secrets are now stored encrypted, the server encrypts the plaintext ones on startup
+ALTER TABLE accounts RENAME COLUMN totp_secret TO totp_secret_plaintext;
+ALTER TABLE accounts ADD COLUMN totp_secret VARCHAR(255) NULL AFTER totp_secret_plaintext;
@@ -0,0 +1,13 @@
+ALTER TABLE accounts
+    ADD COLUMN role ENUM('none', 'editor', 'admin', 'superadmin') DEFAULT 'none', -- HACK: https://github.com/launchbadge/sqlx/issues/3750
+    ADD COLUMN co_op ENUM('S4L', 'STUDIO_MATIC') NULL;
+-- Accounts keep only their highest role, losing access to the co-ops of the others
+UPDATE accounts
+    JOIN role_assignments ON role_assignments.id = (
+        SELECT id FROM role_assignments
+            WHERE account_id = accounts.id
+            ORDER BY role DESC, co_op IS NULL DESC
+            LIMIT 1
+    )
+    SET accounts.role = role_assignments.role, accounts.co_op = role_assignments.co_op;
+DROP TABLE role_assignments;
@@ -0,0 +1,14 @@
+-- Roles per co-op, an assignment without a co-op applies to all of them
+CREATE TABLE IF NOT EXISTS role_assignments (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    account_id BIGINT UNSIGNED NOT NULL,
+    co_op ENUM('S4L', 'STUDIO_MATIC') NULL,
+    role ENUM('none', 'editor', 'admin', 'superadmin') DEFAULT 'none', -- HACK: https://github.com/launchbadge/sqlx/issues/3750
+    UNIQUE INDEX role_assignments_account_co_op_index (account_id, (IFNULL(co_op, ''))),
+    FOREIGN KEY (account_id) REFERENCES accounts (id) ON DELETE CASCADE ON UPDATE CASCADE
+);
+INSERT INTO role_assignments (account_id, co_op, role)
+    SELECT id, co_op, role FROM accounts WHERE deleted_at IS NULL;
+ALTER TABLE accounts
+    DROP COLUMN co_op,
+    DROP COLUMN role;
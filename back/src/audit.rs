@@ -0,0 +1,99 @@
+use crate::ErrorResponse;
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{self, IntoResponse},
+};
+use serde::Serialize;
+use sqlx::MySqlExecutor;
+
+#[derive(utoipa::OpenApi)]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(get::openapi());
+    api
+}
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "AUDIT_")]
+pub enum Error {
+    #[error("Could not format time")]
+    TimeFormat(#[from] time::error::Format),
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> response::Response {
+        let status = match self {
+            Self::TimeFormat(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+/// Changes to accounts made by someone else, to what roles may do and to the settings
+#[derive(Clone, Copy, Debug, strum::Display)]
+pub enum Action {
+    #[strum(serialize = "users.role")]
+    RoleChange,
+    #[strum(serialize = "permissions")]
+    PermissionsChange,
+    #[strum(serialize = "settings")]
+    SettingsChange,
+    #[strum(serialize = "users.unlock")]
+    Unlock,
+    #[strum(serialize = "users.suspend")]
+    Suspend,
+    #[strum(serialize = "users.reactivate")]
+    Reactivate,
+    #[strum(serialize = "users.delete")]
+    Delete,
+    #[strum(serialize = "users.impersonate")]
+    ImpersonationStart,
+    #[strum(serialize = "users.impersonate.end")]
+    ImpersonationEnd,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = audit::Response)]
+struct Response {
+    id: u64,
+    /// Account that made the change, kept after it is deleted
+    actor_id: Option<u64>,
+    action: String,
+    /// Account that was changed, kept after it is deleted
+    target_id: Option<u64>,
+    #[schema(value_type = Object)]
+    details: serde_json::Value,
+    created_at: String,
+}
+
+pub async fn record(
+    executor: impl MySqlExecutor<'_>,
+    actor_id: Option<u64>,
+    action: Action,
+    target_id: Option<u64>,
+    details: serde_json::Value,
+) -> sqlx::Result<()> {
+    let _ = sqlx::query(
+        "INSERT INTO audit_log (actor_id, action, target_id, details) VALUES (?, ?, ?, ?)",
+    )
+    .bind(actor_id)
+    .bind(action.to_string())
+    .bind(target_id)
+    .bind(details.to_string())
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub mod get;
@@ -0,0 +1,76 @@
+use crate::{
+    ApiResult, AppState,
+    audit::{Error, Response},
+    permissions::{Require, require},
+};
+use axum::{Json, extract::State, response::IntoResponse};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+#[derive(sqlx::FromRow)]
+struct Entry {
+    id: u64,
+    actor_id: Option<u64>,
+    action: String,
+    target_id: Option<u64>,
+    details: String,
+    created_at: OffsetDateTime,
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(audit_log))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    get,
+    path = "/audit-log",
+    security(("session" = ["audit_log.read"])),
+    description = "Changes made to accounts by others, newest first",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Response>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn audit_log(
+    State(AppState { pool, .. }): State<AppState>,
+    _: Require<require::AuditLogRead>,
+) -> ApiResult<impl IntoResponse> {
+    let entries: Vec<Entry> = sqlx::query_as(
+        "SELECT id, actor_id, action, target_id, details, created_at
+            FROM audit_log
+            ORDER BY id DESC",
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(Error::Database)?;
+
+    Ok(Json(
+        entries
+            .into_iter()
+            .map(|entry| {
+                Ok(Response {
+                    id: entry.id,
+                    actor_id: entry.actor_id,
+                    action: entry.action,
+                    target_id: entry.target_id,
+                    details: serde_json::from_str(&entry.details).unwrap_or_default(),
+                    created_at: entry.created_at.to_utc().format(&Rfc3339)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?,
+    ))
+}
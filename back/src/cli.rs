@@ -0,0 +1,163 @@
+use crate::users::{
+    self, Assignment, Role,
+    auth::{self, invite, signin::lockout, signup},
+    email::EmailAddress,
+    password::{self, BreachedPasswords, Hasher},
+};
+use clap::{Parser, Subcommand};
+use sqlx::MySqlPool;
+use std::{
+    error::Error,
+    io::{self, BufRead, IsTerminal},
+    num::NonZeroU32,
+    time::Duration,
+};
+
+#[derive(Parser)]
+#[command(about = "Studio-matic API server and administration tool")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Default)]
+pub enum Command {
+    /// Run pending migrations and serve the API (default)
+    #[default]
+    Serve,
+    /// Run pending database migrations
+    Migrate,
+    /// Create a superadmin account
+    CreateSuperadmin { email: EmailAddress },
+    /// Generate an invite code for any role
+    Invite {
+        role: Role,
+        /// Hours until the invite expires [default: one week]
+        #[arg(long)]
+        expires_in_hours: Option<NonZeroU32>,
+        /// How many accounts may sign up with the invite [default: 1]
+        #[arg(long)]
+        max_uses: Option<NonZeroU32>,
+        /// Only allow signing up with this email, which the invite is sent to
+        #[arg(long)]
+        email: Option<EmailAddress>,
+    },
+    /// List or revoke sessions
+    #[command(subcommand)]
+    Sessions(SessionsCommand),
+    /// Set a new password for an account and revoke its sessions
+    ResetPassword { email: EmailAddress },
+    /// Lift the signin lockout of an email, whether or not it has an account
+    Unlock { email: EmailAddress },
+}
+
+#[derive(Subcommand)]
+pub enum SessionsCommand {
+    /// List active sessions, optionally only those of one account
+    List { email: Option<EmailAddress> },
+    /// Revoke all sessions of an account
+    Revoke { email: EmailAddress },
+}
+
+pub async fn run(pool: &MySqlPool, command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Migrate => {
+            crate::migrate(pool).await?;
+            println!("Database is up to date");
+        }
+        Command::CreateSuperadmin { email } => {
+            let password = read_password()?;
+            password::check(pool, &BreachedPasswords::from_env(), &email, &password).await?;
+            let id = signup::create_account(
+                pool,
+                &Hasher::from_env(),
+                &email,
+                &password,
+                Assignment {
+                    role: Role::SuperAdmin,
+                    co_op: None,
+                },
+                None,
+                true,
+            )
+            .await?;
+            println!("Created superadmin {email} with id {id}");
+        }
+        Command::Invite {
+            role,
+            expires_in_hours,
+            max_uses,
+            email,
+        } => {
+            // Links are only needed when the invite is emailed
+            let frontend_url = match email {
+                Some(_) => {
+                    crate::frontend_url().ok_or("FRONTEND_URL must be set to email invites")?
+                }
+                None => String::new(),
+            };
+            let code = invite::create_invite(
+                pool,
+                Assignment { role, co_op: None },
+                expires_in_hours.map(|hours| Duration::from_hours(hours.get().into())),
+                max_uses,
+                email.as_ref(),
+                None,
+                &frontend_url,
+            )
+            .await?;
+            println!("{code}");
+        }
+        Command::Sessions(SessionsCommand::List { email }) => {
+            let account_id = match email {
+                Some(email) => Some(users::find_by_email(pool, &email).await?),
+                None => None,
+            };
+            for auth::SessionInfo {
+                id,
+                email,
+                created_at,
+                last_seen_at,
+                expires_at,
+                ip,
+                user_agent,
+            } in auth::list_sessions(pool, account_id).await?
+            {
+                println!(
+                    "{id}\t{email}\t{created_at}\t{last_seen_at}\t{expires_at}\t{}\t{}",
+                    ip.unwrap_or_default(),
+                    user_agent.unwrap_or_default(),
+                );
+            }
+        }
+        Command::Sessions(SessionsCommand::Revoke { email }) => {
+            let id = users::find_by_email(pool, &email).await?;
+            let revoked = auth::end_sessions(pool, auth::Sessions::Account(id)).await?;
+            println!("Revoked {revoked} sessions of {email}");
+        }
+        Command::ResetPassword { email } => {
+            let id = users::find_by_email(pool, &email).await?;
+            let password = read_password()?;
+            password::check(pool, &BreachedPasswords::from_env(), &email, &password).await?;
+            users::set_password(pool, &Hasher::from_env(), id, &password).await?;
+            let revoked = auth::end_sessions(pool, auth::Sessions::Account(id)).await?;
+            println!("Reset password of {email} and revoked {revoked} sessions");
+        }
+        Command::Unlock { email } => {
+            let cleared = lockout::clear_failures(pool, &email).await?;
+            println!("Unlocked {email} by forgetting {cleared} failed signins");
+        }
+    }
+    Ok(())
+}
+
+fn read_password() -> io::Result<String> {
+    if io::stdin().is_terminal() {
+        rpassword::prompt_password("Password: ")
+    } else {
+        let mut password = String::new();
+        let _ = io::stdin().lock().read_line(&mut password)?;
+        Ok(password.trim_end_matches(['\r', '\n']).to_owned())
+    }
+}
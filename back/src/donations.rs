@@ -64,9 +64,24 @@ pub struct Request {
     co_op: CoOp,
 }
 
-#[derive(Deserialize, Type, utoipa::ToSchema)]
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Debug,
+    Serialize,
+    Deserialize,
+    Type,
+    utoipa::ToSchema,
+    strum::AsRefStr,
+    strum::VariantArray,
+)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 #[sqlx(rename_all = "SCREAMING_SNAKE_CASE")]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
 pub enum CoOp {
     S4l,
     StudioMatic,
@@ -1,6 +1,7 @@
 use crate::{
     ApiError, ApiResult, AppState, donations,
-    users::{Role, auth::validate},
+    permissions::{Require, require},
+    users::auth::validate::Member,
 };
 use axum::{
     extract::{Path, State},
@@ -20,6 +21,7 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[utoipa::path(
     delete,
     path = "/donations/{id}",
+    security(("session" = ["donations.delete"]), ("api_token" = ["donations:write"])),
     responses(
         (
             status = StatusCode::NO_CONTENT,
@@ -36,21 +38,20 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     )
 )]
 pub async fn donation(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::DonationsDelete, Member>,
     Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
-
-    Ok(sqlx::query("DELETE FROM donations WHERE id = ? LIMIT 1")
-        .bind(id)
-        .execute(&pool)
-        .await
-        .map_err(donations::Error::Database)?
-        .rows_affected()
-        .ne(&0)
-        .then_some(StatusCode::NO_CONTENT)
-        .ok_or(donations::Error::NotFound)?)
+    Ok(
+        sqlx::query("DELETE FROM donations WHERE id = ? AND FIND_IN_SET(co_op, ?) LIMIT 1")
+            .bind(id)
+            .bind(member.co_op_set())
+            .execute(&pool)
+            .await
+            .map_err(donations::Error::Database)?
+            .rows_affected()
+            .ne(&0)
+            .then_some(StatusCode::NO_CONTENT)
+            .ok_or(donations::Error::NotFound)?,
+    )
 }
@@ -1,7 +1,8 @@
 use crate::{
     ApiError, ApiResult, AppState,
     donations::{self, Response},
-    users::{Role, auth::validate},
+    permissions::{Require, require},
+    users::auth::validate::Member,
 };
 use axum::{
     Json,
@@ -21,6 +22,7 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[utoipa::path(
     get,
     path = "/donations",
+    security(("session" = ["donations.read"]), ("api_token" = ["donations:read"])),
     responses(
         (
             status = StatusCode::OK,
@@ -34,17 +36,16 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     ),
 )]
 pub async fn donations(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::DonationsRead, Member>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
-
     Ok(Json(
         sqlx::query_as::<_, (u64, u64, OffsetDateTime, f64, String)>(
-            "SELECT id, coins, donated_at, income_eur, co_op FROM donations",
+            "SELECT id, coins, donated_at, income_eur, co_op
+                FROM donations
+                WHERE FIND_IN_SET(co_op, ?)",
         )
+        .bind(member.co_op_set())
         .fetch_all(&pool)
         .await
         .map_err(donations::Error::Database)?
@@ -68,6 +69,7 @@ pub async fn donations(
 #[utoipa::path(
     get,
     path = "/donations/{id}",
+    security(("session" = ["donations.read"]), ("api_token" = ["donations:read"])),
     responses(
         (
             status = StatusCode::OK,
@@ -85,19 +87,19 @@ pub async fn donations(
     ),
 )]
 pub async fn donation(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::DonationsRead, Member>,
     Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
-
     let (id, coins, donated_at, income_eur, co_op): (u64, u64, OffsetDateTime, f64, String) =
         sqlx::query_as(
-            "SELECT id, coins, donated_at, income_eur, co_op FROM donations WHERE id = ? LIMIT 1",
+            "SELECT id, coins, donated_at, income_eur, co_op
+                FROM donations
+                WHERE id = ? AND FIND_IN_SET(co_op, ?)
+                LIMIT 1",
         )
         .bind(id)
+        .bind(member.co_op_set())
         .fetch_optional(&pool)
         .await
         .map_err(donations::Error::Database)?
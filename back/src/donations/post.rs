@@ -1,7 +1,8 @@
 use crate::{
     ApiError, ApiResult, AppState,
     donations::{self, Request},
-    users::{Role, auth::validate},
+    permissions::{Require, require},
+    users::auth::validate::Member,
 };
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
 use axum_extra::extract::WithRejection as Rejectable;
@@ -24,6 +25,7 @@ struct IdResponse {
 #[utoipa::path(
     post,
     path = "/donations",
+    security(("session" = ["donations.write"]), ("api_token" = ["donations:write"])),
     responses(
         (
             status = StatusCode::CREATED,
@@ -34,12 +36,16 @@ struct IdResponse {
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions or limited to another co-op",
+        ),
         (status = StatusCode::INTERNAL_SERVER_ERROR)
     )
 )]
 pub async fn donation(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::DonationsWrite, Member>,
     Rejectable(
         Json(Request {
             coins,
@@ -49,9 +55,7 @@ pub async fn donation(
         _,
     ): Rejectable<Json<Request>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
+    member.require_co_op(co_op)?;
 
     let id = sqlx::query(
         "INSERT INTO donations (coins, income_eur, co_op)
@@ -1,7 +1,8 @@
 use crate::{
     ApiError, ApiResult, AppState,
     donations::{self, Request},
-    users::{Role, auth::validate},
+    permissions::{Require, require},
+    users::auth::validate::Member,
 };
 use axum::{
     Json,
@@ -22,6 +23,7 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[utoipa::path(
     put,
     path = "/donations/{id}",
+    security(("session" = ["donations.write"]), ("api_token" = ["donations:write"])),
     responses(
         (
             status = StatusCode::OK,
@@ -34,12 +36,16 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions or limited to another co-op",
+        ),
         (status = StatusCode::INTERNAL_SERVER_ERROR)
     )
 )]
 pub async fn donation(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::DonationsWrite, Member>,
     Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
     Rejectable(
         Json(Request {
@@ -50,22 +56,23 @@ pub async fn donation(
         _,
     ): Rejectable<Json<Request>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
+    member.require_co_op(co_op)?;
 
-    Ok(
-        sqlx::query("UPDATE donations SET coins = ?, income_eur = ?, co_op = ? WHERE id = ?")
-            .bind(coins)
-            .bind(income_eur)
-            .bind(co_op)
-            .bind(id)
-            .execute(&pool)
-            .await
-            .map_err(donations::Error::Database)?
-            .rows_affected()
-            .ne(&0)
-            .then_some(StatusCode::OK)
-            .ok_or(donations::Error::NotFound)?,
+    Ok(sqlx::query(
+        "UPDATE donations
+                SET coins = ?, income_eur = ?, co_op = ?
+                WHERE id = ? AND FIND_IN_SET(co_op, ?)",
     )
+    .bind(coins)
+    .bind(income_eur)
+    .bind(co_op)
+    .bind(id)
+    .bind(member.co_op_set())
+    .execute(&pool)
+    .await
+    .map_err(donations::Error::Database)?
+    .rows_affected()
+    .ne(&0)
+    .then_some(StatusCode::OK)
+    .ok_or(donations::Error::NotFound)?)
 }
@@ -1,23 +1,36 @@
+mod audit;
+mod cli;
 mod donations;
+mod mail;
+mod permissions;
 mod supporters;
+#[cfg(test)]
+mod tests;
 use axum::{
     Json, Router,
     extract::rejection,
     http::{self, HeaderValue, Method, header, request::Parts},
+    middleware,
     response::{IntoResponse, Response},
     routing,
 };
 mod health;
+mod settings;
 mod users;
+use clap::Parser;
 use serde::Serialize;
 use sqlx::MySqlPool;
-use std::{env, net::SocketAddr};
+use std::{env, net::SocketAddr, sync::Arc};
 use tokio::net::TcpListener;
 use tower_governor::{GovernorLayer, governor::GovernorConfig};
 use tower_http::cors::{AllowOrigin, CorsLayer};
+use users::auth::TokenHasher;
 use utoipa::{
     PartialSchema, ToSchema,
-    openapi::{Components, RefOr, Schema},
+    openapi::{
+        Components, RefOr, Schema,
+        security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
+    },
 };
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -26,48 +39,247 @@ struct ApiDoc;
 fn openapi() -> utoipa::openapi::OpenApi {
     use utoipa::OpenApi;
     let mut api = ApiDoc::openapi();
-    let _ = api
-        .components
-        .insert(Components::builder().schema_from::<ApiError>().build());
+    // Scopes of the security requirements name the required permissions,
+    // respectively the required scopes of API tokens
+    let _ = api.components.insert(
+        Components::builder()
+            .schema_from::<ApiError>()
+            .security_scheme(
+                "session",
+                SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("session_token"))),
+            )
+            .security_scheme(
+                "api_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            )
+            .build(),
+    );
     api.merge(users::auth::invite::openapi());
+    api.merge(users::auth::invites::openapi());
+    api.merge(users::auth::magic_link::openapi());
+    api.merge(users::auth::oidc::openapi());
+    api.merge(users::auth::passkey::openapi());
+    api.merge(users::auth::password_reset::openapi());
     api.merge(users::auth::signup::openapi());
     api.merge(users::auth::signin::openapi());
+    api.merge(users::auth::signin::totp::openapi());
     api.merge(users::auth::signout::openapi());
     api.merge(users::auth::validate::openapi());
+    api.merge(users::auth::verify_email::openapi());
     api.merge(users::me::openapi());
     api.merge(users::openapi());
     api.merge(health::openapi());
     api.merge(donations::openapi());
     api.merge(supporters::openapi());
+    api.merge(settings::openapi());
+    api.merge(audit::openapi());
+    api.merge(permissions::openapi());
     api
 }
 
 #[tokio::main]
 async fn main() {
+    let cli::Cli { command } = cli::Cli::parse();
+
     let pool = MySqlPool::connect(&env::var("DATABASE_URL").expect("DATABASE_URL must be set"))
         .await
         .expect("Unable to connect to mysql database");
 
-    sqlx::migrate!()
-        .run(&pool)
+    match command.unwrap_or_default() {
+        cli::Command::Serve => serve(pool).await,
+        command => {
+            if let Err(e) = cli::run(&pool, command).await {
+                eprintln!("Error: {e}");
+                let mut source = e.source();
+                while let Some(e) = source {
+                    eprintln!("Caused by: {e}");
+                    source = e.source();
+                }
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+pub async fn migrate(pool: &MySqlPool) -> Result<(), sqlx::migrate::MigrateError> {
+    sqlx::migrate!().run(pool).await
+}
+
+/// Base of links sent in emails, only optional in debug builds
+pub fn frontend_url() -> Option<String> {
+    if let Ok(v) = env::var("FRONTEND_URL") {
+        Some(v.trim_end_matches('/').to_owned())
+    } else {
+        #[cfg(not(debug_assertions))]
+        return None;
+        #[allow(unreachable_code)]
+        Some("http://[::]:8080".to_owned())
+    }
+}
+
+async fn serve(pool: MySqlPool) {
+    migrate(&pool)
         .await
         .expect("Unable to perform mysql database migrations");
 
-    tokio::spawn(users::auth::cleanup_expired_sessions(pool.clone()));
+    tokio::spawn(users::auth::cleanup_expired_tokens(pool.clone()));
+    tokio::spawn(mail::deliver_outbox(pool.clone(), mail::Mail::from_env()));
+
+    let token_hasher = TokenHasher::new(&if let Ok(v) = env::var("TOKEN_SECRET") {
+        assert!(v.len() >= 32, "TOKEN_SECRET must be at least 32 bytes long");
+        v.into_bytes()
+    } else {
+        #[cfg(not(debug_assertions))]
+        panic!("TOKEN_SECRET must be set");
+        #[allow(unreachable_code)]
+        rand::random::<[u8; 32]>().to_vec()
+    });
+    users::totp::encrypt_plaintext_secrets(&pool, &token_hasher)
+        .await
+        .expect("Unable to encrypt two-factor authentication secrets");
+
+    let state = AppState {
+        pool,
+        token_hasher,
+        frontend_url: frontend_url().expect("FRONTEND_URL must be set"),
+        password_hasher: users::password::Hasher::from_env(),
+        breached_passwords: users::password::BreachedPasswords::from_env(),
+        oidc_providers: Arc::new(users::auth::oidc::Providers::from_env()),
+    };
 
     let app = Router::new()
         .merge(SwaggerUi::new("/").url("/api-docs/openapi.json", openapi()))
         .route("/health", routing::head(health::health))
         .route("/users", routing::get(users::get::users))
         .route("/users/{id}", routing::get(users::get::user))
+        .route("/users/{id}", routing::patch(users::patch::user))
         .route("/users/{id}", routing::delete(users::delete::user))
+        .route("/users/{id}/unlock", routing::post(users::unlock::unlock))
+        .route(
+            "/users/{id}/suspend",
+            routing::post(users::suspend::suspend),
+        )
+        .route(
+            "/users/{id}/reactivate",
+            routing::post(users::suspend::reactivate),
+        )
+        .route(
+            "/users/{id}/impersonate",
+            routing::post(users::impersonate::impersonate),
+        )
         .route("/users/auth/invite", routing::post(users::auth::invite))
+        .route(
+            "/users/auth/invites",
+            routing::get(users::auth::invites::get::invites),
+        )
+        .route(
+            "/users/auth/invites/{id}",
+            routing::delete(users::auth::invites::delete::invite),
+        )
+        .route(
+            "/users/auth/magic-link",
+            routing::post(users::auth::magic_link),
+        )
+        .route(
+            "/users/auth/magic-link/redeem",
+            routing::post(users::auth::magic_link::redeem::redeem),
+        )
+        .route(
+            "/users/auth/oidc/{provider}/start",
+            routing::get(users::auth::oidc::start::start),
+        )
+        .route(
+            "/users/auth/oidc/{provider}/callback",
+            routing::get(users::auth::oidc::callback::callback),
+        )
+        .route("/users/auth/passkey", routing::post(users::auth::passkey))
+        .route(
+            "/users/auth/passkey/confirm",
+            routing::post(users::auth::passkey::confirm::confirm),
+        )
+        .route(
+            "/users/auth/password-reset",
+            routing::post(users::auth::password_reset),
+        )
+        .route(
+            "/users/auth/password-reset/confirm",
+            routing::post(users::auth::password_reset::confirm::confirm),
+        )
         .route("/users/auth/signup", routing::post(users::auth::signup))
         .route("/users/auth/signin", routing::post(users::auth::signin))
+        .route(
+            "/users/auth/signin/totp",
+            routing::post(users::auth::signin::totp::totp),
+        )
         .route("/users/auth/signout", routing::delete(users::auth::signout))
         .route("/users/auth/validate", routing::get(users::auth::validate))
+        .route(
+            "/users/auth/verify-email",
+            routing::post(users::auth::verify_email),
+        )
         .route("/users/me", routing::get(users::me::get::me))
         .route("/users/me", routing::patch(users::me::patch::me))
+        .route(
+            "/users/me/sessions",
+            routing::get(users::me::sessions::get::sessions),
+        )
+        .route(
+            "/users/me/sessions",
+            routing::delete(users::me::sessions::delete::sessions),
+        )
+        .route(
+            "/users/me/sessions/{id}",
+            routing::delete(users::me::sessions::delete::session),
+        )
+        .route(
+            "/users/me/passkeys",
+            routing::get(users::me::passkeys::get::passkeys),
+        )
+        .route(
+            "/users/me/passkeys",
+            routing::post(users::me::passkeys::post::passkey),
+        )
+        .route(
+            "/users/me/passkeys/confirm",
+            routing::post(users::me::passkeys::confirm::confirm),
+        )
+        .route(
+            "/users/me/passkeys/{id}",
+            routing::delete(users::me::passkeys::delete::passkey),
+        )
+        .route(
+            "/users/me/reauthenticate",
+            routing::post(users::me::reauthenticate::reauthenticate),
+        )
+        .route(
+            "/users/me/verify-email",
+            routing::post(users::me::verify_email::verify_email),
+        )
+        .route(
+            "/users/me/tokens",
+            routing::get(users::me::tokens::get::tokens),
+        )
+        .route(
+            "/users/me/tokens",
+            routing::post(users::me::tokens::post::token),
+        )
+        .route(
+            "/users/me/tokens/{id}",
+            routing::delete(users::me::tokens::delete::token),
+        )
+        .route("/users/me/totp", routing::post(users::me::totp::post::totp))
+        .route(
+            "/users/me/totp",
+            routing::delete(users::me::totp::delete::totp),
+        )
+        .route(
+            "/users/me/totp/confirm",
+            routing::post(users::me::totp::confirm::confirm),
+        )
+        .route(
+            "/users/me/totp/recovery-codes",
+            routing::post(users::me::totp::recovery_codes::recovery_codes),
+        )
         .route("/donations", routing::get(donations::get::donations))
         .route("/donations/{id}", routing::get(donations::get::donation))
         .route("/donations", routing::post(donations::post::donation))
@@ -84,7 +296,16 @@ async fn main() {
             "/supporters/{id}",
             routing::delete(supporters::delete::supporter),
         )
-        .with_state(AppState { pool })
+        .route("/settings", routing::get(settings::get::settings))
+        .route("/audit-log", routing::get(audit::get::audit_log))
+        .route("/permissions", routing::get(permissions::get::permissions))
+        .route(
+            "/permissions/{role}",
+            routing::put(permissions::put::permissions),
+        )
+        .route("/settings", routing::put(settings::put::settings))
+        .layer(middleware::from_fn(users::auth::renew_session))
+        .with_state(state)
         .layer(GovernorLayer::new(GovernorConfig::default()))
         .layer(
             CorsLayer::new()
@@ -145,8 +366,30 @@ pub enum ApiError {
     Signin(#[from] users::auth::signin::Error),
     #[error("Could not sign up: {0}")]
     Signup(#[from] users::auth::signup::Error),
+    #[error("Could not verify email: {0}")]
+    VerifyEmail(#[from] users::auth::verify_email::Error),
+    #[error("Could not sign in with link: {0}")]
+    MagicLink(#[from] users::auth::magic_link::Error),
+    #[error("Could not sign in with provider: {0}")]
+    Oidc(#[from] users::auth::oidc::Error),
+    #[error("Could not set password: {0}")]
+    Password(#[from] users::password::Error),
+    #[error("Could not reset password: {0}")]
+    PasswordReset(#[from] users::auth::password_reset::Error),
     #[error("Could not retreive user data: {0}")]
     UserData(#[from] users::Error),
+    #[error("Could not manage API tokens: {0}")]
+    ApiToken(#[from] users::me::tokens::Error),
+    #[error("Could not authenticate with passkey: {0}")]
+    Passkey(#[from] users::passkey::Error),
+    #[error("Could not perform two-factor authentication: {0}")]
+    Totp(#[from] users::totp::Error),
+    #[error("Could not access audit log: {0}")]
+    Audit(#[from] audit::Error),
+    #[error("Could not manage permissions: {0}")]
+    Permissions(#[from] permissions::Error),
+    #[error("Could not access settings: {0}")]
+    Settings(#[from] settings::Error),
     #[error("Could not get donations: {0}")]
     Donation(#[from] donations::Error),
     #[error("Could not get supporters: {0}")]
@@ -155,6 +398,8 @@ pub enum ApiError {
     Json(#[from] rejection::JsonRejection),
     #[error("Could not match path: {0}")]
     Path(#[from] rejection::PathRejection),
+    #[error("Could not deserialize query: {0}")]
+    Query(#[from] rejection::QueryRejection),
 }
 
 impl IntoResponse for ApiError {
@@ -164,7 +409,18 @@ impl IntoResponse for ApiError {
             ApiError::Invite(e) => e.into_response(),
             ApiError::Signin(e) => e.into_response(),
             ApiError::Signup(e) => e.into_response(),
+            ApiError::VerifyEmail(e) => e.into_response(),
+            ApiError::MagicLink(e) => e.into_response(),
+            ApiError::Oidc(e) => e.into_response(),
+            ApiError::Password(e) => e.into_response(),
+            ApiError::PasswordReset(e) => e.into_response(),
             ApiError::UserData(e) => e.into_response(),
+            ApiError::ApiToken(e) => e.into_response(),
+            ApiError::Passkey(e) => e.into_response(),
+            ApiError::Totp(e) => e.into_response(),
+            ApiError::Audit(e) => e.into_response(),
+            ApiError::Permissions(e) => e.into_response(),
+            ApiError::Settings(e) => e.into_response(),
             ApiError::Donation(e) => e.into_response(),
             ApiError::Supporter(e) => e.into_response(),
             ApiError::Json(ref e) => {
@@ -177,6 +433,11 @@ impl IntoResponse for ApiError {
                 let message = self.to_string();
                 (e.status(), Json(ErrorResponse { error, message })).into_response()
             }
+            ApiError::Query(ref e) => {
+                let error = self.as_ref().to_string();
+                let message = self.to_string();
+                (e.status(), Json(ErrorResponse { error, message })).into_response()
+            }
         }
     }
 }
@@ -188,13 +449,24 @@ impl PartialSchema for ApiError {
             <users::auth::invite::Error as strum::VariantNames>::VARIANTS,
             <users::auth::signin::Error as strum::VariantNames>::VARIANTS,
             <users::auth::signup::Error as strum::VariantNames>::VARIANTS,
+            <users::auth::verify_email::Error as strum::VariantNames>::VARIANTS,
+            <users::auth::magic_link::Error as strum::VariantNames>::VARIANTS,
+            <users::auth::oidc::Error as strum::VariantNames>::VARIANTS,
+            <users::password::Error as strum::VariantNames>::VARIANTS,
+            <users::auth::password_reset::Error as strum::VariantNames>::VARIANTS,
             <users::Error as strum::VariantNames>::VARIANTS,
+            <users::me::tokens::Error as strum::VariantNames>::VARIANTS,
+            <users::passkey::Error as strum::VariantNames>::VARIANTS,
+            <users::totp::Error as strum::VariantNames>::VARIANTS,
+            <audit::Error as strum::VariantNames>::VARIANTS,
+            <permissions::Error as strum::VariantNames>::VARIANTS,
+            <settings::Error as strum::VariantNames>::VARIANTS,
             <donations::Error as strum::VariantNames>::VARIANTS,
             <supporters::Error as strum::VariantNames>::VARIANTS,
         ]
         .into_iter()
         .flat_map(IntoIterator::into_iter)
-        .chain(["JSON_REJECTION", "PATH_REJECTION"].iter())
+        .chain(["JSON_REJECTION", "PATH_REJECTION", "QUERY_REJECTION"].iter())
         .map(ToString::to_string)
         .collect::<Vec<String>>();
 
@@ -224,4 +496,10 @@ impl ToSchema for ApiError {
 #[derive(Clone)]
 pub struct AppState {
     pool: MySqlPool,
+    token_hasher: TokenHasher,
+    /// Base of links sent in emails
+    frontend_url: String,
+    password_hasher: users::password::Hasher,
+    breached_passwords: users::password::BreachedPasswords,
+    oidc_providers: Arc<users::auth::oidc::Providers>,
 }
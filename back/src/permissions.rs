@@ -0,0 +1,286 @@
+use crate::{
+    ApiError, AppState, ErrorResponse,
+    users::{
+        Role, Roles,
+        auth::validate::{self, Member, Session},
+    },
+};
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{StatusCode, request::Parts},
+    response::{self, IntoResponse},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySqlExecutor, MySqlPool};
+use std::marker::PhantomData;
+
+#[derive(utoipa::OpenApi)]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(get::openapi());
+    api.merge(put::openapi());
+    api
+}
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "PERMISSIONS_")]
+pub enum Error {
+    #[error("Superadmins always have every permission")]
+    SuperAdmin,
+    #[error("Managing permissions, impersonating and deleting accounts is reserved to superadmins")]
+    Reserved,
+    #[error("Viewers can only be granted read permissions")]
+    ReadOnly,
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> response::Response {
+        let status = match self {
+            Self::SuperAdmin => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Reserved => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::ReadOnly => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+/// What a role may do, granted per role in the `role_permissions` table
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    utoipa::ToSchema,
+    Debug,
+    strum::Display,
+    strum::EnumString,
+    strum::VariantArray,
+)]
+pub enum Permission {
+    #[serde(rename = "donations.read")]
+    #[strum(serialize = "donations.read")]
+    DonationsRead,
+    #[serde(rename = "donations.write")]
+    #[strum(serialize = "donations.write")]
+    DonationsWrite,
+    #[serde(rename = "donations.delete")]
+    #[strum(serialize = "donations.delete")]
+    DonationsDelete,
+    #[serde(rename = "supporters.read")]
+    #[strum(serialize = "supporters.read")]
+    SupportersRead,
+    #[serde(rename = "supporters.write")]
+    #[strum(serialize = "supporters.write")]
+    SupportersWrite,
+    #[serde(rename = "supporters.delete")]
+    #[strum(serialize = "supporters.delete")]
+    SupportersDelete,
+    #[serde(rename = "users.read")]
+    #[strum(serialize = "users.read")]
+    UsersRead,
+    /// Change roles below the own one
+    #[serde(rename = "users.role")]
+    #[strum(serialize = "users.role")]
+    UsersRole,
+    /// Suspend and reactivate accounts below the own role
+    #[serde(rename = "users.suspend")]
+    #[strum(serialize = "users.suspend")]
+    UsersSuspend,
+    /// Reserved to superadmins, as it allows acting as another account
+    #[serde(rename = "users.impersonate")]
+    #[strum(serialize = "users.impersonate")]
+    UsersImpersonate,
+    /// Reserved to superadmins, as deleted accounts cannot be restored
+    #[serde(rename = "users.delete")]
+    #[strum(serialize = "users.delete")]
+    UsersDelete,
+    /// Lift lockouts of accounts below the own role
+    #[serde(rename = "users.unlock")]
+    #[strum(serialize = "users.unlock")]
+    UsersUnlock,
+    /// Invite accounts with roles below the own one
+    #[serde(rename = "users.invite")]
+    #[strum(serialize = "users.invite")]
+    UsersInvite,
+    #[serde(rename = "settings.read")]
+    #[strum(serialize = "settings.read")]
+    SettingsRead,
+    #[serde(rename = "settings.write")]
+    #[strum(serialize = "settings.write")]
+    SettingsWrite,
+    #[serde(rename = "audit_log.read")]
+    #[strum(serialize = "audit_log.read")]
+    AuditLogRead,
+    #[serde(rename = "permissions.read")]
+    #[strum(serialize = "permissions.read")]
+    PermissionsRead,
+    /// Reserved to superadmins, as it allows granting every other permission
+    #[serde(rename = "permissions.write")]
+    #[strum(serialize = "permissions.write")]
+    PermissionsWrite,
+    /// Has to enroll a second factor if the settings require it
+    #[serde(rename = "totp.required")]
+    #[strum(serialize = "totp.required")]
+    TotpRequired,
+}
+
+impl Permission {
+    /// Never granted to roles, only superadmins have it
+    pub fn is_reserved(self) -> bool {
+        matches!(
+            self,
+            Self::PermissionsWrite | Self::UsersDelete | Self::UsersImpersonate
+        )
+    }
+
+    pub fn is_read(self) -> bool {
+        matches!(
+            self,
+            Self::DonationsRead
+                | Self::SupportersRead
+                | Self::UsersRead
+                | Self::SettingsRead
+                | Self::AuditLogRead
+                | Self::PermissionsRead
+        )
+    }
+}
+
+/// Returns whether the role has the permission, superadmins have every permission
+/// and viewers never more than read permissions
+pub async fn granted(
+    executor: impl MySqlExecutor<'_>,
+    role: Role,
+    permission: Permission,
+) -> sqlx::Result<bool> {
+    match role {
+        Role::SuperAdmin => return Ok(true),
+        Role::None if !permission.is_read() => return Ok(false),
+        _ => {}
+    }
+
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM role_permissions WHERE role = ? AND permission = ?)",
+    )
+    .bind(role)
+    .bind(permission.to_string())
+    .fetch_one(executor)
+    .await
+}
+
+/// `Permission` as a type, for `Require`
+pub trait Required {
+    const PERMISSION: Permission;
+}
+
+macro_rules! required {
+    ($($permission:ident),* $(,)?) => {
+        $(
+            pub struct $permission;
+
+            impl Required for $permission {
+                const PERMISSION: Permission = Permission::$permission;
+            }
+        )*
+    };
+}
+
+pub mod require {
+    use super::{Permission, Required};
+
+    required!(
+        DonationsRead,
+        DonationsWrite,
+        DonationsDelete,
+        SupportersRead,
+        SupportersWrite,
+        SupportersDelete,
+        UsersRead,
+        UsersRole,
+        UsersSuspend,
+        UsersImpersonate,
+        UsersDelete,
+        UsersUnlock,
+        UsersInvite,
+        SettingsRead,
+        SettingsWrite,
+        AuditLogRead,
+        PermissionsRead,
+        PermissionsWrite,
+    );
+}
+
+/// Extractors that authenticate roles
+pub trait Authenticated {
+    /// Returns whether any of the roles has the permission
+    fn authorize(
+        &mut self,
+        pool: &MySqlPool,
+        permission: Permission,
+    ) -> impl Future<Output = sqlx::Result<bool>> + Send;
+}
+
+impl Authenticated for Roles {
+    async fn authorize(&mut self, pool: &MySqlPool, permission: Permission) -> sqlx::Result<bool> {
+        self.grants(pool, permission).await
+    }
+}
+
+impl Authenticated for Session {
+    async fn authorize(&mut self, pool: &MySqlPool, permission: Permission) -> sqlx::Result<bool> {
+        self.roles.grants(pool, permission).await
+    }
+}
+
+/// Resolves the role per co-op, keeping the co-ops whose records the permission applies to
+impl Authenticated for Member {
+    async fn authorize(&mut self, pool: &MySqlPool, permission: Permission) -> sqlx::Result<bool> {
+        self.co_ops = self.roles.granted_co_ops(pool, permission).await?;
+        Ok(!self.co_ops.is_empty())
+    }
+}
+
+/// Rejects requests whose roles lack the permission `P`, authenticating them with `A`,
+/// i.e. with a session cookie or an API token for `Roles` and `Member`, only a session cookie for `Session`
+pub struct Require<P, A = Roles>(pub A, pub PhantomData<P>);
+
+impl<P, A> FromRequestParts<AppState> for Require<P, A>
+where
+    P: Required,
+    A: FromRequestParts<AppState, Rejection = ApiError> + Authenticated + Send,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let mut authenticated = A::from_request_parts(parts, state).await?;
+
+        if !authenticated
+            .authorize(&state.pool, P::PERMISSION)
+            .await
+            .map_err(validate::Error::Database)?
+        {
+            Err(validate::Error::InsufficientPermissions)?
+        }
+
+        Ok(Self(authenticated, PhantomData))
+    }
+}
+
+pub mod get;
+pub mod put;
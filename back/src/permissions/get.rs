@@ -0,0 +1,74 @@
+use crate::{
+    ApiResult, AppState,
+    permissions::{Error, Permission, Require, require},
+    users::Role,
+};
+use axum::{Json, extract::State, response::IntoResponse};
+use serde::Serialize;
+use strum::VariantArray;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(permissions))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = permissions::Response)]
+pub struct Response {
+    role: Role,
+    permissions: Vec<Permission>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/permissions",
+    description = "Permissions granted to each role",
+    security(("session" = ["permissions.read"])),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Response>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn permissions(
+    State(AppState { pool, .. }): State<AppState>,
+    _: Require<require::PermissionsRead>,
+) -> ApiResult<impl IntoResponse> {
+    let granted: Vec<(Role, String)> =
+        sqlx::query_as("SELECT role, permission FROM role_permissions")
+            .fetch_all(&pool)
+            .await
+            .map_err(Error::Database)?;
+
+    Ok(Json(
+        [Role::None, Role::Editor, Role::Admin, Role::SuperAdmin]
+            .into_iter()
+            .map(|role| Response {
+                role,
+                permissions: Permission::VARIANTS
+                    .iter()
+                    .copied()
+                    .filter(|permission| {
+                        role == Role::SuperAdmin
+                            || granted.iter().any(|(granted_role, granted)| {
+                                *granted_role == role && *granted == permission.to_string()
+                            })
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>(),
+    ))
+}
@@ -0,0 +1,99 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    audit::{self, Action},
+    permissions::{Error, Permission, Require, require},
+    users::{Role, auth::validate::Session},
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde_json::json;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(permissions))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    put,
+    path = "/permissions/{role}",
+    description = "Replace the permissions granted to a role",
+    security(("session" = ["permissions.write"])),
+    request_body = Vec<Permission>,
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Superadmin role, reserved permission or write permission for viewers",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions or reauthentication required",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn permissions(
+    State(AppState { pool, .. }): State<AppState>,
+    Require(session, _): Require<require::PermissionsWrite, Session>,
+    Rejectable(Path(role), _): Rejectable<Path<Role>, ApiError>,
+    Rejectable(Json(permissions), _): Rejectable<Json<Vec<Permission>>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    if role == Role::SuperAdmin {
+        Err(Error::SuperAdmin)?
+    }
+    if permissions
+        .iter()
+        .any(|permission| permission.is_reserved())
+    {
+        Err(Error::Reserved)?
+    }
+    if role == Role::None && !permissions.iter().all(|permission| permission.is_read()) {
+        Err(Error::ReadOnly)?
+    }
+
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    let _ = sqlx::query("DELETE FROM role_permissions WHERE role = ?")
+        .bind(role)
+        .execute(&mut *transaction)
+        .await
+        .map_err(Error::Database)?;
+    for permission in &permissions {
+        let _ = sqlx::query("INSERT IGNORE INTO role_permissions (role, permission) VALUES (?, ?)")
+            .bind(role)
+            .bind(permission.to_string())
+            .execute(&mut *transaction)
+            .await
+            .map_err(Error::Database)?;
+    }
+
+    audit::record(
+        &mut *transaction,
+        Some(session.account_id),
+        Action::PermissionsChange,
+        None,
+        json!({ "role": role, "permissions": permissions }),
+    )
+    .await
+    .map_err(Error::Database)?;
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,81 @@
+use crate::ErrorResponse;
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{self, IntoResponse},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlExecutor;
+
+#[derive(utoipa::OpenApi)]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(get::openapi());
+    api.merge(put::openapi());
+    api
+}
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "SETTINGS_")]
+pub enum Error {
+    #[error("Lockout threshold must be at least 1")]
+    InvalidThreshold,
+    #[error("Password lengths must be at least 1, the maximum not below the minimum")]
+    InvalidPasswordLength,
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> response::Response {
+        let status = match self {
+            Self::InvalidThreshold => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InvalidPasswordLength => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+#[derive(Serialize, Deserialize, sqlx::FromRow, utoipa::ToSchema)]
+#[schema(as = settings::Settings)]
+pub struct Settings {
+    /// Reject roles with the `totp.required` permission, e.g. admins, and superadmins
+    /// without two-factor authentication
+    pub require_totp: bool,
+    /// Failed signins within an hour after which an account is locked temporarily,
+    /// IP addresses are locked after four times as many
+    #[schema(minimum = 1)]
+    pub signin_lockout_threshold: u32,
+    /// Answer failed signins with the same error whether or not the account exists
+    pub signin_generic_errors: bool,
+    /// Minimum number of characters in new passwords
+    #[schema(minimum = 1)]
+    pub password_min_length: u32,
+    /// Maximum number of characters in new passwords, bounding the cost of hashing them
+    #[schema(minimum = 1)]
+    pub password_max_length: u32,
+    /// Reject new passwords equal to the email or its local part
+    pub password_disallow_email: bool,
+}
+
+pub async fn load(executor: impl MySqlExecutor<'_>) -> sqlx::Result<Settings> {
+    sqlx::query_as(
+        "SELECT require_totp, signin_lockout_threshold, signin_generic_errors,
+                password_min_length, password_max_length, password_disallow_email
+            FROM settings
+            LIMIT 1",
+    )
+    .fetch_one(executor)
+    .await
+}
+
+pub mod get;
+pub mod put;
@@ -0,0 +1,45 @@
+use crate::{
+    ApiResult, AppState,
+    permissions::{Require, require},
+    settings::{self, Settings},
+};
+use axum::{Json, extract::State, response::IntoResponse};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(settings))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    get,
+    path = "/settings",
+    security(("session" = ["settings.read"])),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Settings,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn settings(
+    State(AppState { pool, .. }): State<AppState>,
+    _: Require<require::SettingsRead>,
+) -> ApiResult<impl IntoResponse> {
+    Ok(Json(
+        settings::load(&pool)
+            .await
+            .map_err(settings::Error::Database)?,
+    ))
+}
@@ -0,0 +1,103 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    audit::{self, Action},
+    permissions::{Require, require},
+    settings::{self, Settings},
+    users::auth::validate::Session,
+};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde_json::json;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(settings))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    put,
+    path = "/settings",
+    description = "Replace the settings, recording the old and new values in the audit log",
+    security(("session" = ["settings.write"])),
+    responses(
+        (
+            status = StatusCode::OK,
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid lockout threshold or password lengths",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions or reauthentication required",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn settings(
+    State(AppState { pool, .. }): State<AppState>,
+    Require(session, _): Require<require::SettingsWrite, Session>,
+    Rejectable(Json(new), _): Rejectable<Json<Settings>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    let Settings {
+        require_totp,
+        signin_lockout_threshold,
+        signin_generic_errors,
+        password_min_length,
+        password_max_length,
+        password_disallow_email,
+    } = new;
+    if signin_lockout_threshold < 1 {
+        Err(settings::Error::InvalidThreshold)?
+    }
+    if password_min_length < 1 || password_max_length < password_min_length {
+        Err(settings::Error::InvalidPasswordLength)?
+    }
+
+    let mut transaction = pool.begin().await.map_err(settings::Error::Database)?;
+
+    let old = settings::load(&mut *transaction)
+        .await
+        .map_err(settings::Error::Database)?;
+
+    let _ = sqlx::query(
+        "UPDATE settings
+            SET require_totp = ?, signin_lockout_threshold = ?, signin_generic_errors = ?,
+                password_min_length = ?, password_max_length = ?, password_disallow_email = ?",
+    )
+    .bind(require_totp)
+    .bind(signin_lockout_threshold)
+    .bind(signin_generic_errors)
+    .bind(password_min_length)
+    .bind(password_max_length)
+    .bind(password_disallow_email)
+    .execute(&mut *transaction)
+    .await
+    .map_err(settings::Error::Database)?;
+
+    audit::record(
+        &mut *transaction,
+        Some(session.account_id),
+        Action::SettingsChange,
+        None,
+        json!({ "old": old, "new": new }),
+    )
+    .await
+    .map_err(settings::Error::Database)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(settings::Error::Database)?;
+
+    Ok(StatusCode::OK)
+}
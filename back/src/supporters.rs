@@ -1,10 +1,11 @@
-use crate::ErrorResponse;
+use crate::{ApiResult, ErrorResponse, donations::CoOp, users::auth::validate::Member};
 use axum::{
     Json,
     http::StatusCode,
     response::{self, IntoResponse},
 };
 use serde::{Deserialize, Serialize};
+use sqlx::MySqlExecutor;
 
 #[derive(utoipa::OpenApi)]
 struct ApiDoc;
@@ -24,6 +25,8 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 pub enum Error {
     #[error("Supporter not found")]
     NotFound,
+    #[error("Donation not found")]
+    DonationNotFound,
     #[error("Could not query database")]
     Database(#[from] sqlx::Error),
 }
@@ -32,6 +35,7 @@ impl IntoResponse for Error {
     fn into_response(self) -> response::Response {
         let status = match self {
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::DonationNotFound => StatusCode::NOT_FOUND,
             Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
@@ -57,6 +61,22 @@ pub struct Request {
     donation_id: u64,
 }
 
+/// Rejects donations of co-ops in which the checked permission is not granted
+async fn require_donation_co_op(
+    executor: impl MySqlExecutor<'_>,
+    member: &Member,
+    donation_id: u64,
+) -> ApiResult<()> {
+    let co_op: CoOp = sqlx::query_scalar("SELECT co_op FROM donations WHERE id = ? LIMIT 1")
+        .bind(donation_id)
+        .fetch_optional(executor)
+        .await
+        .map_err(Error::Database)?
+        .ok_or(Error::DonationNotFound)?;
+
+    Ok(member.require_co_op(co_op)?)
+}
+
 pub mod delete;
 pub mod get;
 pub mod post;
@@ -1,6 +1,8 @@
 use crate::{
-    ApiError, ApiResult, AppState, supporters,
-    users::{Role, auth::validate},
+    ApiError, ApiResult, AppState,
+    permissions::{Require, require},
+    supporters,
+    users::auth::validate::Member,
 };
 use axum::{
     extract::{Path, State},
@@ -20,6 +22,7 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[utoipa::path(
     delete,
     path = "/supporters/{id}",
+    security(("session" = ["supporters.delete"]), ("api_token" = ["supporters:write"])),
     responses(
         (
             status = StatusCode::NO_CONTENT,
@@ -36,21 +39,22 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     )
 )]
 pub async fn supporter(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::SupportersDelete, Member>,
     Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
-
-    Ok(sqlx::query("DELETE FROM supporters WHERE id = ? LIMIT 1")
-        .bind(id)
-        .execute(&pool)
-        .await
-        .map_err(supporters::Error::Database)?
-        .rows_affected()
-        .ne(&0)
-        .then_some(StatusCode::NO_CONTENT)
-        .ok_or(supporters::Error::NotFound)?)
+    Ok(sqlx::query(
+        "DELETE supporters
+            FROM supporters JOIN donations ON donations.id = supporters.donation_id
+            WHERE supporters.id = ? AND FIND_IN_SET(donations.co_op, ?)",
+    )
+    .bind(id)
+    .bind(member.co_op_set())
+    .execute(&pool)
+    .await
+    .map_err(supporters::Error::Database)?
+    .rows_affected()
+    .ne(&0)
+    .then_some(StatusCode::NO_CONTENT)
+    .ok_or(supporters::Error::NotFound)?)
 }
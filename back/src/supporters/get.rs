@@ -1,7 +1,8 @@
 use crate::{
     ApiError, ApiResult, AppState,
+    permissions::{Require, require},
     supporters::{self, Response},
-    users::{Role, auth::validate},
+    users::auth::validate::Member,
 };
 use axum::{
     Json,
@@ -21,6 +22,7 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[utoipa::path(
     get,
     path = "/supporters",
+    security(("session" = ["supporters.read"]), ("api_token" = ["supporters:read"])),
     responses(
         (
             status = StatusCode::OK,
@@ -34,18 +36,18 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     ),
 )]
 pub async fn supporters(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::SupportersRead, Member>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
-
-    let supporters: Vec<(u64, String, u64)> =
-        sqlx::query_as("SELECT id, name, donation_id FROM supporters")
-            .fetch_all(&pool)
-            .await
-            .map_err(supporters::Error::Database)?;
+    let supporters: Vec<(u64, String, u64)> = sqlx::query_as(
+        "SELECT supporters.id, supporters.name, supporters.donation_id
+                FROM supporters JOIN donations ON donations.id = supporters.donation_id
+                WHERE FIND_IN_SET(donations.co_op, ?)",
+    )
+    .bind(member.co_op_set())
+    .fetch_all(&pool)
+    .await
+    .map_err(supporters::Error::Database)?;
 
     Ok(Json(
         supporters
@@ -64,6 +66,7 @@ pub async fn supporters(
 #[utoipa::path(
     get,
     path = "/supporters/{id}",
+    security(("session" = ["supporters.read"]), ("api_token" = ["supporters:read"])),
     responses(
         (
             status = StatusCode::OK,
@@ -81,18 +84,18 @@ pub async fn supporters(
     ),
 )]
 pub async fn supporter(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::SupportersRead, Member>,
     Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
-
     let (id, name, donation_id): (u64, String, u64) = sqlx::query_as(
-        "SELECT id, name, donation_id FROM supporters WHERE supporters.id = ? LIMIT 1",
+        "SELECT supporters.id, supporters.name, supporters.donation_id
+            FROM supporters JOIN donations ON donations.id = supporters.donation_id
+            WHERE supporters.id = ? AND FIND_IN_SET(donations.co_op, ?)
+            LIMIT 1",
     )
     .bind(id)
+    .bind(member.co_op_set())
     .fetch_optional(&pool)
     .await
     .map_err(supporters::Error::Database)?
@@ -1,7 +1,8 @@
 use crate::{
     ApiError, ApiResult, AppState,
+    permissions::{Require, require},
     supporters::{self, Request},
-    users::{Role, auth::validate},
+    users::auth::validate::Member,
 };
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
 use axum_extra::extract::WithRejection as Rejectable;
@@ -24,27 +25,34 @@ struct IdResponse {
 #[utoipa::path(
     post,
     path = "/supporters",
+    security(("session" = ["supporters.write"]), ("api_token" = ["supporters:write"])),
     responses(
         (
             status = StatusCode::CREATED,
             body = IdResponse,
             description = "Successfully added supporter",
         ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Donation not found",
+        ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions or donation of another co-op",
+        ),
         (status = StatusCode::INTERNAL_SERVER_ERROR)
     )
 )]
 pub async fn supporter(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::SupportersWrite, Member>,
     Rejectable(Json(Request { name, donation_id }), _): Rejectable<Json<Request>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
+    supporters::require_donation_co_op(&pool, &member, donation_id).await?;
 
     let id = sqlx::query(
         "INSERT INTO supporters (name, donation_id)
@@ -1,7 +1,8 @@
 use crate::{
     ApiError, ApiResult, AppState,
+    permissions::{Require, require},
     supporters::{self, Request},
-    users::{Role, auth::validate},
+    users::auth::validate::Member,
 };
 use axum::{
     Json,
@@ -22,6 +23,7 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[utoipa::path(
     put,
     path = "/supporters/{id}",
+    security(("session" = ["supporters.write"]), ("api_token" = ["supporters:write"])),
     responses(
         (
             status = StatusCode::OK,
@@ -29,36 +31,41 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
         ),
         (
             status = StatusCode::NOT_FOUND,
-            description = "Supporter not found",
+            description = "Supporter or donation not found",
         ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions or donation of another co-op",
+        ),
         (status = StatusCode::INTERNAL_SERVER_ERROR)
     )
 )]
 pub async fn supporter(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    Require(member, _): Require<require::SupportersWrite, Member>,
     Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
     Rejectable(Json(Request { name, donation_id }), _): Rejectable<Json<Request>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Editor {
-        Err(validate::Error::InsufficientPermissions)?
-    }
+    supporters::require_donation_co_op(&pool, &member, donation_id).await?;
 
-    Ok(
-        sqlx::query("UPDATE supporters  SET  name = ?, donation_id = ? WHERE id = ?")
-            .bind(name)
-            .bind(donation_id)
-            .bind(id)
-            .execute(&pool)
-            .await
-            .map_err(supporters::Error::Database)?
-            .rows_affected()
-            .ne(&0)
-            .then_some(StatusCode::OK)
-            .ok_or(supporters::Error::NotFound)?,
+    Ok(sqlx::query(
+        "UPDATE supporters JOIN donations ON donations.id = supporters.donation_id
+                SET supporters.name = ?, supporters.donation_id = ?
+                WHERE supporters.id = ? AND FIND_IN_SET(donations.co_op, ?)",
     )
+    .bind(name)
+    .bind(donation_id)
+    .bind(id)
+    .bind(member.co_op_set())
+    .execute(&pool)
+    .await
+    .map_err(supporters::Error::Database)?
+    .rows_affected()
+    .ne(&0)
+    .then_some(StatusCode::OK)
+    .ok_or(supporters::Error::NotFound)?)
 }
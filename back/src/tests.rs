@@ -0,0 +1,47 @@
+use crate::{
+    AppState,
+    users::{self, auth::TokenHasher},
+};
+use argon2::Params;
+use axum::{body, response::Response};
+use sqlx::MySqlPool;
+use std::sync::Arc;
+
+pub const FRONTEND_URL: &str = "http://frontend.test";
+
+/// State around a pool, with password hashing cheap enough for tests
+pub fn app_state(pool: MySqlPool) -> AppState {
+    AppState {
+        pool,
+        token_hasher: TokenHasher::new(b"test"),
+        frontend_url: FRONTEND_URL.to_owned(),
+        password_hasher: users::password::Hasher::new(
+            Params::new(
+                Params::MIN_M_COST,
+                Params::MIN_T_COST,
+                Params::MIN_P_COST,
+                None,
+            )
+            .expect("Minimal Argon2 parameters are valid"),
+        ),
+        breached_passwords: users::password::BreachedPasswords::from_env(),
+        oidc_providers: Arc::new(users::auth::oidc::Providers::from_env()),
+    }
+}
+
+/// Pool for paths that fail before querying, it never connects
+pub fn unconnected_pool() -> MySqlPool {
+    MySqlPool::connect_lazy("mysql://localhost/unused").expect("URL is valid")
+}
+
+/// The `error` code of an error response
+pub async fn error_code(response: Response) -> String {
+    let body = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("Body is readable");
+    let body: serde_json::Value = serde_json::from_slice(&body).expect("Body is JSON");
+    body["error"]
+        .as_str()
+        .expect("Body has an error code")
+        .to_owned()
+}
@@ -1,4 +1,9 @@
-use crate::ErrorResponse;
+use crate::{
+    ErrorResponse,
+    donations::CoOp,
+    permissions::{self, Permission},
+    users::auth::validate,
+};
 use argon2::password_hash;
 use axum::{
     Json,
@@ -6,12 +11,16 @@ use axum::{
     response::{self, IntoResponse},
 };
 use serde::{Deserialize, Serialize};
-use sqlx::Type;
+use sqlx::{Acquire, MySql, MySqlExecutor, MySqlPool, Row, Type, mysql::MySqlRow};
+use strum::VariantArray;
 
 pub mod email;
+pub mod password;
 
 pub mod auth;
 pub mod me;
+pub mod passkey;
+pub mod totp;
 
 #[derive(utoipa::OpenApi)]
 struct ApiDoc;
@@ -19,7 +28,11 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     use utoipa::OpenApi;
     let mut api = ApiDoc::openapi();
     api.merge(get::openapi());
+    api.merge(patch::openapi());
     api.merge(delete::openapi());
+    api.merge(unlock::openapi());
+    api.merge(suspend::openapi());
+    api.merge(impersonate::openapi());
     api
 }
 
@@ -29,6 +42,16 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 pub enum Error {
     #[error("Account not found")]
     NotFound,
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Cannot remove the last superadmin")]
+    LastSuperAdmin,
+    #[error("A reason is required")]
+    MissingReason,
+    #[error("Each co-op can only be assigned one role, superadmins cannot be limited to a co-op")]
+    InvalidRoles,
+    #[error("Could not format time")]
+    TimeFormat(#[from] time::error::Format),
     #[error("Could not hash password")]
     PasswordHash(#[from] password_hash::Error),
     #[error("Could not query database")]
@@ -39,6 +62,11 @@ impl IntoResponse for Error {
     fn into_response(self) -> response::Response {
         let status = match self {
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::SessionNotFound => StatusCode::NOT_FOUND,
+            Self::LastSuperAdmin => StatusCode::CONFLICT,
+            Self::MissingReason => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InvalidRoles => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::TimeFormat(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::PasswordHash(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
@@ -55,8 +83,55 @@ impl IntoResponse for Error {
 struct Response {
     id: u64,
     email: String,
+    /// Highest of the roles, `none` without any
     role: Role,
     role_rank: u8,
+    roles: Vec<Assignment>,
+    totp_enabled: bool,
+    email_verified: bool,
+    magic_link_enabled: bool,
+    /// Signin and access are rejected until reactivated
+    suspended: bool,
+    suspension_reason: Option<String>,
+    /// Anonymized, only kept for what refers to it
+    deleted: bool,
+}
+
+/// Selects the columns of `Response`, to be followed by a `WHERE` clause
+const SELECT_RESPONSE: &str = "SELECT id, email,
+        CAST((SELECT JSON_ARRAYAGG(JSON_OBJECT('role', role, 'co_op', co_op))
+            FROM role_assignments WHERE account_id = accounts.id) AS CHAR) AS roles,
+        totp_enabled_at IS NOT NULL AS totp_enabled,
+        email_verified_at IS NOT NULL AS email_verified,
+        magic_link_enabled,
+        suspended_at IS NOT NULL AS suspended, suspension_reason,
+        deleted_at IS NOT NULL AS deleted
+    FROM accounts";
+
+impl sqlx::FromRow<'_, MySqlRow> for Response {
+    fn from_row(row: &MySqlRow) -> sqlx::Result<Self> {
+        let roles: Vec<Assignment> = match row.try_get::<Option<String>, _>("roles")? {
+            Some(roles) => serde_json::from_str(&roles).map_err(|e| sqlx::Error::ColumnDecode {
+                index: "roles".to_owned(),
+                source: Box::new(e),
+            })?,
+            None => Vec::new(),
+        };
+        let role = Roles::new(roles.clone()).highest().unwrap_or(Role::None);
+        Ok(Self {
+            id: row.try_get("id")?,
+            email: row.try_get("email")?,
+            role,
+            role_rank: role.rank(),
+            roles,
+            totp_enabled: row.try_get("totp_enabled")?,
+            email_verified: row.try_get("email_verified")?,
+            magic_link_enabled: row.try_get("magic_link_enabled")?,
+            suspended: row.try_get("suspended")?,
+            suspension_reason: row.try_get("suspension_reason")?,
+            deleted: row.try_get("deleted")?,
+        })
+    }
 }
 
 #[derive(
@@ -71,27 +146,265 @@ struct Response {
     Ord,
     utoipa::ToSchema,
     Debug,
+    strum::Display,
+    strum::EnumString,
 )]
 #[serde(rename_all = "lowercase")]
 #[sqlx(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
 pub enum Role {
+    /// Viewer, limited to read permissions
     None,
     Editor,
     Admin,
     SuperAdmin,
 }
 
-// HACK: to implement hierarchical `>` and `<` for `WHERE` clauses
-impl From<Role> for u8 {
-    fn from(role: Role) -> Self {
-        match role {
-            Role::None => 1,
-            Role::Editor => 2,
-            Role::Admin => 3,
-            Role::SuperAdmin => 4,
+impl Role {
+    /// Position in the hierarchy, for clients to compare roles
+    pub fn rank(self) -> u8 {
+        match self {
+            Self::None => 1,
+            Self::Editor => 2,
+            Self::Admin => 3,
+            Self::SuperAdmin => 4,
+        }
+    }
+}
+
+/// Role of an account in a co-op, or in all co-ops if `co_op` is unset
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Debug,
+    Serialize,
+    Deserialize,
+    sqlx::FromRow,
+    utoipa::ToSchema,
+)]
+#[schema(as = users::Assignment)]
+pub struct Assignment {
+    pub role: Role,
+    pub co_op: Option<CoOp>,
+}
+
+/// Roles assigned to an account, stored in the `role_assignments` table
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Roles(Vec<Assignment>);
+
+impl Roles {
+    pub fn new(mut assignments: Vec<Assignment>) -> Self {
+        assignments.sort();
+        Self(assignments)
+    }
+
+    pub fn assignments(&self) -> &[Assignment] {
+        &self.0
+    }
+
+    /// Rejects assigning a co-op twice and limiting superadmins to a co-op
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut co_ops: Vec<_> = self.0.iter().map(|assignment| assignment.co_op).collect();
+        co_ops.sort();
+        co_ops.dedup();
+        if co_ops.len() != self.0.len()
+            || self
+                .0
+                .iter()
+                .any(|assignment| assignment.role == Role::SuperAdmin && assignment.co_op.is_some())
+        {
+            Err(Error::InvalidRoles)?
+        }
+        Ok(())
+    }
+
+    /// Role in a co-op, the higher of the one for it and the one for all co-ops,
+    /// or only the latter without a co-op
+    pub fn in_co_op(&self, co_op: Option<CoOp>) -> Option<Role> {
+        self.0
+            .iter()
+            .filter(|assignment| assignment.co_op.is_none() || assignment.co_op == co_op)
+            .map(|assignment| assignment.role)
+            .max()
+    }
+
+    /// Role for what is not limited to a co-op, e.g. managing accounts of some of them
+    pub fn highest(&self) -> Option<Role> {
+        self.0.iter().map(|assignment| assignment.role).max()
+    }
+
+    pub fn is_superadmin(&self) -> bool {
+        self.in_co_op(None) == Some(Role::SuperAdmin)
+    }
+
+    /// Returns whether any of the roles has the permission
+    pub async fn grants(&self, pool: &MySqlPool, permission: Permission) -> sqlx::Result<bool> {
+        for assignment in &self.0 {
+            if permissions::granted(pool, assignment.role, permission).await? {
+                return Ok(true);
+            }
         }
+        Ok(false)
+    }
+
+    /// Returns the co-ops in which the role has the permission
+    pub async fn granted_co_ops(
+        &self,
+        pool: &MySqlPool,
+        permission: Permission,
+    ) -> sqlx::Result<Vec<CoOp>> {
+        let mut co_ops = Vec::new();
+        for &co_op in CoOp::VARIANTS {
+            if let Some(role) = self.in_co_op(Some(co_op))
+                && permissions::granted(pool, role, permission).await?
+            {
+                co_ops.push(co_op);
+            }
+        }
+        Ok(co_ops)
+    }
+
+    pub async fn load(executor: impl MySqlExecutor<'_>, account_id: u64) -> sqlx::Result<Self> {
+        Ok(Self::new(
+            sqlx::query_as("SELECT role, co_op FROM role_assignments WHERE account_id = ?")
+                .bind(account_id)
+                .fetch_all(executor)
+                .await?,
+        ))
+    }
+
+    /// Replaces the roles of an account
+    pub async fn store(
+        &self,
+        connection: impl Acquire<'_, Database = MySql>,
+        account_id: u64,
+    ) -> sqlx::Result<()> {
+        let mut transaction = connection.begin().await?;
+
+        let _ = sqlx::query("DELETE FROM role_assignments WHERE account_id = ?")
+            .bind(account_id)
+            .execute(&mut *transaction)
+            .await?;
+        for Assignment { role, co_op } in &self.0 {
+            let _ = sqlx::query(
+                "INSERT INTO role_assignments (account_id, co_op, role) VALUES (?, ?, ?)",
+            )
+            .bind(account_id)
+            .bind(co_op)
+            .bind(role)
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await
+    }
+}
+
+pub async fn find_by_email(
+    executor: impl MySqlExecutor<'_>,
+    email: &email::EmailAddress,
+) -> Result<u64, Error> {
+    sqlx::query_scalar("SELECT id FROM accounts WHERE email = ? LIMIT 1")
+        .bind(email)
+        .fetch_optional(executor)
+        .await?
+        .ok_or(Error::NotFound)
+}
+
+pub async fn set_password(
+    executor: impl MySqlExecutor<'_>,
+    password_hasher: &password::Hasher,
+    id: u64,
+    password: &str,
+) -> Result<(), Error> {
+    let _ = sqlx::query("UPDATE accounts SET password = ? WHERE id = ? LIMIT 1")
+        .bind(password_hasher.hash(password)?)
+        .bind(id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Rejects taking the superadmin role or access from an account unless another active superadmin
+/// is left to manage the rest
+pub async fn require_other_superadmin(
+    executor: impl MySqlExecutor<'_>,
+    id: u64,
+) -> Result<(), Error> {
+    let others: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*)
+            FROM accounts JOIN role_assignments ON role_assignments.account_id = accounts.id
+            WHERE role_assignments.role = ? AND role_assignments.co_op IS NULL
+                AND accounts.id != ? AND accounts.suspended_at IS NULL
+                AND accounts.deleted_at IS NULL
+            FOR UPDATE",
+    )
+    .bind(Role::SuperAdmin)
+    .bind(id)
+    .fetch_one(executor)
+    .await?;
+    if others == 0 {
+        Err(Error::LastSuperAdmin)?
+    }
+    Ok(())
+}
+
+/// Rejects roles not below the own one in their co-op, e.g. to be invited or assigned
+pub fn require_lower_role(
+    session: &validate::Session,
+    Assignment { role, co_op }: Assignment,
+) -> Result<(), validate::Error> {
+    match session.roles.in_co_op(co_op) {
+        None => Err(validate::Error::OtherCoOp),
+        Some(own) if role >= own => Err(validate::Error::InsufficientPermissions),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Rejects managing accounts with a role not below the own one in any of their co-ops,
+/// accounts without roles count as viewers in all co-ops
+pub fn require_manageable(
+    session: &validate::Session,
+    roles: &Roles,
+) -> Result<(), validate::Error> {
+    const VIEWER: Assignment = Assignment {
+        role: Role::None,
+        co_op: None,
+    };
+    match roles.assignments() {
+        [] => require_lower_role(session, VIEWER),
+        assignments => assignments
+            .iter()
+            .try_for_each(|assignment| require_lower_role(session, *assignment)),
+    }
+}
+
+/// Upgrades the hash of a just verified password to the current algorithm and parameters,
+/// without failing the signin if that is not possible
+pub async fn rehash_password(
+    executor: impl MySqlExecutor<'_>,
+    password_hasher: &password::Hasher,
+    id: u64,
+    hash: &str,
+    password: &str,
+) {
+    if password_hasher.needs_rehash(hash)
+        && let Err(e) = set_password(executor, password_hasher, id, password).await
+    {
+        eprintln!("Failed to rehash password of account {id}: {e}");
     }
 }
 
 pub mod delete;
 pub mod get;
+pub mod impersonate;
+pub mod patch;
+pub mod suspend;
+pub mod unlock;
+
+#[cfg(test)]
+mod tests;
@@ -1,10 +1,57 @@
+use crate::audit::{self, Action};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request},
+    http::{HeaderValue, header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use hmac::{Hmac, Mac};
 use rand::Rng;
-use sqlx::MySqlPool;
-use std::time::Duration;
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::{Acquire, MySql, MySqlConnection, MySqlExecutor, MySqlPool, QueryBuilder};
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use time::OffsetDateTime;
 
+// Sessions expire after being idle for `*_MAX_AGE` and at the latest `*_MAX_LIFETIME` after signin
 const SESSION_TOKEN_MAX_AGE: Duration = Duration::from_hours(1);
+const SESSION_TOKEN_MAX_LIFETIME: Duration = Duration::from_hours(12);
+const REMEMBER_ME_SESSION_TOKEN_MAX_AGE: Duration = Duration::from_hours(7 * 24);
+const REMEMBER_ME_SESSION_TOKEN_MAX_LIFETIME: Duration = Duration::from_hours(30 * 24);
+/// Impersonation sessions are not renewed, so they end this long after being started
+const IMPERSONATION_SESSION_MAX_LIFETIME: Duration = Duration::from_mins(30);
 
-fn generate_session_token() -> String {
+/// Keyed hash of tokens, so a database leak does not leak usable tokens
+#[derive(Clone)]
+pub struct TokenHasher(Hmac<Sha256>);
+
+impl TokenHasher {
+    pub fn new(secret: &[u8]) -> Self {
+        Self(Hmac::new_from_slice(secret).expect("HMAC accepts keys of any length"))
+    }
+
+    pub fn hash(&self, token: &str) -> String {
+        let mut mac = self.0.clone();
+        mac.update(token.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Key for encrypting data at rest, prefixed with a NUL byte no token contains
+    /// so it never equals a stored hash
+    pub fn derive_key(&self, purpose: &str) -> [u8; 32] {
+        let mut mac = self.0.clone();
+        mac.update(b"\0");
+        mac.update(purpose.as_bytes());
+        mac.finalize().into_bytes().into()
+    }
+}
+
+fn generate_token() -> String {
     rand::rng()
         .sample_iter(&rand::distr::Alphanumeric)
         .take(64)
@@ -12,28 +59,364 @@ fn generate_session_token() -> String {
         .collect()
 }
 
-pub async fn cleanup_expired_sessions(pool: MySqlPool) -> ! {
+/// Where a request came from, recorded alongside sessions
+pub struct Client {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Client {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ip = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|ConnectInfo(addr)| addr.ip().to_canonical().to_string());
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.chars().take(255).collect());
+
+        Ok(Client { ip, user_agent })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub struct SessionInfo {
+    pub id: u64,
+    pub email: String,
+    pub created_at: OffsetDateTime,
+    pub last_seen_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+pub async fn create_session(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    account_id: u64,
+    remember_me: bool,
+    Client { ip, user_agent }: &Client,
+) -> sqlx::Result<(String, Duration)> {
+    let token = generate_token();
+    let max_age = if remember_me {
+        REMEMBER_ME_SESSION_TOKEN_MAX_AGE
+    } else {
+        SESSION_TOKEN_MAX_AGE
+    };
+
+    let _ = sqlx::query(
+        "INSERT INTO sessions (token, account_id, remember_me, ip, user_agent, expires_at)
+            VALUES (?, ?, ?, ?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(token_hasher.hash(&token))
+    .bind(account_id)
+    .bind(remember_me)
+    .bind(ip)
+    .bind(user_agent)
+    .bind(max_age.as_secs())
+    .execute(pool)
+    .await?;
+
+    Ok((token, max_age))
+}
+
+/// Returns the new session's id, token and lifetime
+pub async fn create_impersonation_session(
+    executor: impl MySqlExecutor<'_>,
+    token_hasher: &TokenHasher,
+    account_id: u64,
+    impersonator_id: u64,
+    Client { ip, user_agent }: &Client,
+) -> sqlx::Result<(u64, String, Duration)> {
+    let token = generate_token();
+
+    let id = sqlx::query(
+        "INSERT INTO sessions
+                (token, account_id, impersonator_id, remember_me, ip, user_agent, expires_at)
+            VALUES (?, ?, ?, FALSE, ?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(token_hasher.hash(&token))
+    .bind(account_id)
+    .bind(impersonator_id)
+    .bind(ip)
+    .bind(user_agent)
+    .bind(IMPERSONATION_SESSION_MAX_LIFETIME.as_secs())
+    .execute(executor)
+    .await?
+    .last_insert_id();
+
+    Ok((id, token, IMPERSONATION_SESSION_MAX_LIFETIME))
+}
+
+pub fn session_cookie(token: &str, max_age: Duration) -> String {
+    #[cfg(debug_assertions)]
+    let cookie = format!(
+        "session_token={token}; Max-Age={}; Path=/; HttpOnly",
+        max_age.as_secs()
+    );
+    #[cfg(not(debug_assertions))]
+    let cookie = format!(
+        "session_token={token}; Max-Age={}; Path=/; HttpOnly; Secure; SameSite=None",
+        max_age.as_secs()
+    );
+    cookie
+}
+
+pub fn remove_session_cookie() -> String {
+    session_cookie("", Duration::ZERO)
+}
+
+/// How long the request's session lasts after authenticating it renewed it
+#[derive(Clone, Default)]
+pub struct Renewal(pub Arc<OnceLock<Duration>>);
+
+/// Re-issues the cookie of the request's session if it was renewed
+pub async fn renew_session(mut request: Request, next: Next) -> Response {
+    let Ok(token) = validate::extract_session_token(request.headers()) else {
+        return next.run(request).await;
+    };
+    let Renewal(renewed) = Renewal::default();
+    let _ = request
+        .extensions_mut()
+        .insert(Renewal(Arc::clone(&renewed)));
+
+    let mut response = next.run(request).await;
+
+    let cookie_set = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .any(|v| v.as_bytes().starts_with(b"session_token="));
+    if let Some(max_age) = renewed.get()
+        && !cookie_set
+        && let Ok(cookie) = HeaderValue::from_str(&session_cookie(&token, *max_age))
+    {
+        let _ = response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+
+    response
+}
+
+/// Slides the expiry of an authenticated session unless it ends at `fixed_expiry`,
+/// returning how long it lasts or `None` if it expired meanwhile
+pub async fn renew(
+    pool: &MySqlPool,
+    id: u64,
+    remember_me: bool,
+    created_at: OffsetDateTime,
+    fixed_expiry: Option<OffsetDateTime>,
+) -> sqlx::Result<Option<Duration>> {
+    let now = OffsetDateTime::now_utc();
+    let expires_at = fixed_expiry.unwrap_or_else(|| {
+        let (max_age, max_lifetime) = if remember_me {
+            (
+                REMEMBER_ME_SESSION_TOKEN_MAX_AGE,
+                REMEMBER_ME_SESSION_TOKEN_MAX_LIFETIME,
+            )
+        } else {
+            (SESSION_TOKEN_MAX_AGE, SESSION_TOKEN_MAX_LIFETIME)
+        };
+        (now + max_age).min(created_at + max_lifetime)
+    });
+
+    let renewed = sqlx::query(
+        "UPDATE sessions
+            SET last_seen_at = NOW(), expires_at = ?
+            WHERE id = ? AND expires_at > NOW()
+            LIMIT 1",
+    )
+    .bind(expires_at)
+    .bind(id)
+    .execute(pool)
+    .await?
+    .rows_affected()
+        != 0;
+
+    Ok(renewed.then(|| Duration::try_from(expires_at - now).unwrap_or_default()))
+}
+
+pub async fn cleanup_expired_tokens(pool: MySqlPool) -> ! {
     let mut interval = tokio::time::interval(Duration::from_mins(5));
     loop {
         interval.tick().await;
 
-        match sqlx::query("DELETE FROM sessions WHERE expires_at < NOW()")
-            .execute(&pool)
-            .await
-        {
-            Ok(res) => println!("Deleted {} expired sessions", res.rows_affected()),
+        match end_sessions(&pool, Sessions::Expired).await {
+            Ok(ended) => println!("Deleted {ended} expired sessions"),
             Err(e) => eprintln!("Failed to cleanup expired sessions: {e}"),
         }
+
+        for table in [
+            "signin_challenges",
+            "password_resets",
+            "email_verifications",
+            "signin_failures",
+            "api_tokens",
+            "oidc_states",
+            "magic_links",
+            "passkey_challenges",
+        ] {
+            match sqlx::query(&format!("DELETE FROM {table} WHERE expires_at < NOW()"))
+                .execute(&pool)
+                .await
+            {
+                Ok(res) => println!("Deleted {} expired {table}", res.rows_affected()),
+                Err(e) => eprintln!("Failed to cleanup expired {table}: {e}"),
+            }
+        }
+    }
+}
+
+/// Sessions to end with `end_sessions`
+pub enum Sessions<'a> {
+    /// A session of the account, if it exists
+    One {
+        id: u64,
+        account_id: u64,
+    },
+    /// Every session of the account, including impersonations of it
+    Account(u64),
+    /// Every session of the account except the one with the id
+    AccountExcept {
+        account_id: u64,
+        id: u64,
+    },
+    /// The session with the hashed token, when signing out
+    Token(&'a str),
+    Expired,
+}
+
+/// Deletes sessions, recording the end of impersonations among them in the audit log,
+/// and returns how many were deleted. Every session has to end through this.
+pub async fn end_sessions(
+    connection: impl Acquire<'_, Database = MySql>,
+    sessions: Sessions<'_>,
+) -> sqlx::Result<usize> {
+    let mut transaction = connection.begin().await?;
+
+    let mut query =
+        QueryBuilder::new("SELECT id, account_id, impersonator_id FROM sessions WHERE ");
+    let reason = match sessions {
+        Sessions::One { id, account_id } => {
+            let _ = query
+                .push("id = ")
+                .push_bind(id)
+                .push(" AND account_id = ")
+                .push_bind(account_id);
+            "revoked"
+        }
+        Sessions::Account(account_id) => {
+            let _ = query.push("account_id = ").push_bind(account_id);
+            "revoked"
+        }
+        Sessions::AccountExcept { account_id, id } => {
+            let _ = query
+                .push("account_id = ")
+                .push_bind(account_id)
+                .push(" AND id != ")
+                .push_bind(id);
+            "revoked"
+        }
+        Sessions::Token(token) => {
+            let _ = query.push("token = ").push_bind(token);
+            "signout"
+        }
+        Sessions::Expired => {
+            let _ = query.push("expires_at < NOW()");
+            "expired"
+        }
+    };
+    let ended: Vec<(u64, u64, Option<u64>)> = query
+        .push(" FOR UPDATE")
+        .build_query_as()
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    for &(id, account_id, impersonator_id) in &ended {
+        let _ = sqlx::query("DELETE FROM sessions WHERE id = ? LIMIT 1")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+        if impersonator_id.is_some() {
+            audit::record(
+                &mut *transaction,
+                impersonator_id,
+                Action::ImpersonationEnd,
+                Some(account_id),
+                json!({ "session_id": id, "reason": reason }),
+            )
+            .await?;
+        }
     }
+
+    transaction.commit().await?;
+    Ok(ended.len())
+}
+
+pub async fn list_sessions(
+    pool: &MySqlPool,
+    account_id: Option<u64>,
+) -> sqlx::Result<Vec<SessionInfo>> {
+    sqlx::query_as(
+        "SELECT sessions.id, accounts.email, sessions.created_at, sessions.last_seen_at,
+                sessions.expires_at, sessions.ip, sessions.user_agent
+            FROM sessions JOIN accounts ON accounts.id = sessions.account_id
+            WHERE sessions.expires_at > NOW() AND (? IS NULL OR sessions.account_id = ?)
+            ORDER BY sessions.created_at",
+    )
+    .bind(account_id)
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Replaces the token of a session to prevent session fixation, keeping its expiry
+pub async fn rotate_session(
+    connection: &mut MySqlConnection,
+    token_hasher: &TokenHasher,
+    session_id: u64,
+) -> sqlx::Result<(String, Duration)> {
+    let token = generate_token();
+
+    let _ = sqlx::query("UPDATE sessions SET token = ? WHERE id = ? LIMIT 1")
+        .bind(token_hasher.hash(&token))
+        .bind(session_id)
+        .execute(&mut *connection)
+        .await?;
+
+    let expires_at: OffsetDateTime =
+        sqlx::query_scalar("SELECT expires_at FROM sessions WHERE id = ? LIMIT 1")
+            .bind(session_id)
+            .fetch_one(&mut *connection)
+            .await?;
+
+    Ok((
+        token,
+        Duration::try_from(expires_at - OffsetDateTime::now_utc()).unwrap_or_default(),
+    ))
 }
 
+pub mod api_token;
 pub mod invite;
+pub mod invites;
+pub mod magic_link;
+pub mod oidc;
+pub mod passkey;
+pub mod password_reset;
 pub mod signin;
 pub mod signout;
 pub mod signup;
 pub mod validate;
+pub mod verify_email;
 pub use invite::invite;
+pub use magic_link::magic_link;
+pub use passkey::passkey;
+pub use password_reset::password_reset;
 pub use signin::signin;
 pub use signout::signout;
 pub use signup::signup;
 pub use validate::validate;
+pub use verify_email::verify_email;
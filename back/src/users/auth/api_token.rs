@@ -0,0 +1,159 @@
+use crate::users::{
+    Roles,
+    auth::{TokenHasher, generate_token},
+};
+use axum::http::{HeaderMap, Method, header};
+use serde::{Deserialize, Serialize};
+use sqlx::{MySqlExecutor, MySqlPool};
+use std::time::Duration;
+
+/// What a personal API token may access, on top of the role of its account
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    utoipa::ToSchema,
+    Debug,
+    strum::Display,
+    strum::EnumString,
+)]
+pub enum Scope {
+    #[serde(rename = "donations:read")]
+    #[strum(serialize = "donations:read")]
+    DonationsRead,
+    #[serde(rename = "donations:write")]
+    #[strum(serialize = "donations:write")]
+    DonationsWrite,
+    #[serde(rename = "supporters:read")]
+    #[strum(serialize = "supporters:read")]
+    SupportersRead,
+    #[serde(rename = "supporters:write")]
+    #[strum(serialize = "supporters:write")]
+    SupportersWrite,
+}
+
+impl Scope {
+    /// Scope needed for a request, `None` for routes that API tokens may not use
+    pub fn required(method: &Method, path: &str) -> Option<Self> {
+        let read = method == Method::GET || method == Method::HEAD;
+        match (path.trim_start_matches('/').split('/').next()?, read) {
+            ("donations", true) => Some(Self::DonationsRead),
+            ("donations", false) => Some(Self::DonationsWrite),
+            ("supporters", true) => Some(Self::SupportersRead),
+            ("supporters", false) => Some(Self::SupportersWrite),
+            _ => None,
+        }
+    }
+}
+
+/// Stores scopes as a comma separated list
+fn join_scopes(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn split_scopes(scopes: &str) -> Vec<Scope> {
+    scopes.split(',').filter_map(|s| s.parse().ok()).collect()
+}
+
+pub fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Returns the id and the token, which is only stored hashed
+pub async fn create(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    account_id: u64,
+    name: &str,
+    scopes: &[Scope],
+    expires_in: Duration,
+) -> sqlx::Result<(u64, String)> {
+    let token = generate_token();
+
+    let id = sqlx::query(
+        "INSERT INTO api_tokens (token, account_id, name, scopes, expires_at)
+            VALUES (?, ?, ?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(token_hasher.hash(&token))
+    .bind(account_id)
+    .bind(name)
+    .bind(join_scopes(scopes))
+    .bind(expires_in.as_secs())
+    .execute(pool)
+    .await?
+    .last_insert_id();
+
+    Ok((id, token))
+}
+
+/// Account behind an API token, checked like a session of it would be
+pub struct Owner {
+    pub roles: Roles,
+    pub suspended: bool,
+    pub totp_missing: bool,
+    pub scopes: Vec<Scope>,
+}
+
+/// Returns the token's account and the token's scopes, marking it as used
+pub async fn authenticate(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    token: &str,
+) -> sqlx::Result<Option<Owner>> {
+    let Some((id, account_id, suspended, totp_missing, scopes)): Option<(
+        u64,
+        u64,
+        bool,
+        bool,
+        String,
+    )> = sqlx::query_as(
+        "SELECT api_tokens.id, accounts.id,
+                accounts.suspended_at IS NOT NULL,
+                settings.require_totp AND accounts.totp_enabled_at IS NULL
+                    AND NOT EXISTS(SELECT 1 FROM passkeys WHERE passkeys.account_id = accounts.id),
+                api_tokens.scopes
+            FROM api_tokens JOIN accounts ON accounts.id = api_tokens.account_id
+                CROSS JOIN settings
+            WHERE api_tokens.token = ? AND api_tokens.expires_at > NOW()
+            LIMIT 1",
+    )
+    .bind(token_hasher.hash(token))
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let _ = sqlx::query("UPDATE api_tokens SET last_used_at = NOW() WHERE id = ? LIMIT 1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(Some(Owner {
+        roles: Roles::load(pool, account_id).await?,
+        suspended,
+        totp_missing,
+        scopes: split_scopes(&scopes),
+    }))
+}
+
+/// Deletes every token of an account, e.g. once its role no longer matches their scopes
+pub async fn revoke_all(executor: impl MySqlExecutor<'_>, account_id: u64) -> sqlx::Result<u64> {
+    Ok(sqlx::query("DELETE FROM api_tokens WHERE account_id = ?")
+        .bind(account_id)
+        .execute(executor)
+        .await?
+        .rows_affected())
+}
@@ -1,6 +1,9 @@
 use crate::{
     ApiError, ApiResult, AppState, ErrorResponse,
-    users::{Role, auth::validate},
+    donations::CoOp,
+    mail,
+    permissions::{Require, require},
+    users::{self, Assignment, Role, auth::validate::Session, email::EmailAddress},
 };
 use axum::{
     Json,
@@ -11,6 +14,11 @@ use axum::{
 use axum_extra::extract::WithRejection as Rejectable;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+use std::{num::NonZeroU32, time::Duration};
+
+const INVITE_DEFAULT_MAX_AGE: Duration = Duration::from_hours(7 * 24);
+const INVITE_MAX_AGE: Duration = Duration::from_hours(30 * 24);
 
 #[derive(utoipa::OpenApi)]
 #[openapi(paths(invite))]
@@ -30,6 +38,17 @@ struct Response {
 #[schema(as = invite::Request)]
 pub struct Request {
     role: Role,
+    /// Limit the role to the donations and supporters of a co-op,
+    /// one in which the inviter has a higher role
+    co_op: Option<CoOp>,
+    /// Defaults to one week, at most 30 days
+    #[schema(value_type = Option<u32>, minimum = 1)]
+    expires_in_hours: Option<NonZeroU32>,
+    /// Defaults to a single use
+    #[schema(value_type = Option<u32>, minimum = 1)]
+    max_uses: Option<NonZeroU32>,
+    /// Only allow signing up with this email, which the invite is sent to
+    email: Option<EmailAddress>,
 }
 
 #[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
@@ -38,15 +57,26 @@ pub struct Request {
 pub enum Error {
     #[error("Invite already exists")]
     Conflict,
+    #[error("Invite not found")]
+    NotFound,
+    #[error("Invite may expire in at most 30 days")]
+    InvalidExpiry,
+    #[error("Could not format time")]
+    TimeFormat(#[from] time::error::Format),
     #[error("Could not query database")]
-    DatabaseError(#[from] sqlx::Error),
+    // Keeps the error code `INVITE_DATABASE_ERROR` clients already match on
+    #[strum(serialize = "DATABASE_ERROR")]
+    Database(#[from] sqlx::Error),
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> response::Response {
         let status = match self {
             Self::Conflict => StatusCode::CONFLICT,
-            Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::InvalidExpiry => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::TimeFormat(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
         let error = self.as_ref().to_string();
@@ -59,24 +89,67 @@ impl IntoResponse for Error {
 #[utoipa::path(
     post,
     path = "/users/auth/invite",
+    security(("session" = ["users.invite"])),
     responses(
         (
             status = StatusCode::CREATED,
             description = "Successfully generated invite",
             body = Response,
         ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid expiry",
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
         ),
     ),
 )]
 pub async fn invite(
-    State(AppState { pool }): State<AppState>,
-    requester: Role,
-    Rejectable(Json(Request { role }), _): Rejectable<Json<Request>, ApiError>,
+    State(AppState {
+        pool, frontend_url, ..
+    }): State<AppState>,
+    Require(requester, _): Require<require::UsersInvite, Session>,
+    Rejectable(
+        Json(Request {
+            role,
+            co_op,
+            expires_in_hours,
+            max_uses,
+            email,
+        }),
+        _,
+    ): Rejectable<Json<Request>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if requester < Role::SuperAdmin || role >= Role::SuperAdmin {
-        Err(validate::Error::InsufficientPermissions)?
+    let assignment = Assignment { role, co_op };
+    users::require_lower_role(&requester, assignment)?;
+
+    let code = create_invite(
+        &pool,
+        assignment,
+        expires_in_hours.map(|hours| Duration::from_hours(hours.get().into())),
+        max_uses,
+        email.as_ref(),
+        Some(requester.account_id),
+        &frontend_url,
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(Response { code })))
+}
+
+pub async fn create_invite(
+    pool: &MySqlPool,
+    Assignment { role, co_op }: Assignment,
+    expires_in: Option<Duration>,
+    max_uses: Option<NonZeroU32>,
+    email: Option<&EmailAddress>,
+    invited_by: Option<u64>,
+    frontend_url: &str,
+) -> Result<String, Error> {
+    let expires_in = expires_in.unwrap_or(INVITE_DEFAULT_MAX_AGE);
+    if expires_in > INVITE_MAX_AGE {
+        Err(Error::InvalidExpiry)?
     }
 
     let code: String = rand::rng()
@@ -85,16 +158,42 @@ pub async fn invite(
         .map(char::from)
         .collect();
 
+    let mut transaction = pool.begin().await?;
+
     match sqlx::query(
-        "INSERT INTO invites (role, code, expires_at) VALUES (?, ?, NOW() + INTERVAL 1 WEEK)",
+        "INSERT INTO invites (role, co_op, code, email, max_uses, invited_by, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, NOW() + INTERVAL ? SECOND)",
     )
     .bind(role)
+    .bind(co_op)
     .bind(&code)
-    .execute(&pool)
+    .bind(email)
+    .bind(max_uses.map_or(1, NonZeroU32::get))
+    .bind(invited_by)
+    .bind(expires_in.as_secs())
+    .execute(&mut *transaction)
     .await
     {
         Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(Error::Conflict)?,
-        Err(e) => Err(Error::DatabaseError(e))?,
-        Ok(_) => Ok((StatusCode::CREATED, Json(Response { code }))),
+        Err(e) => Err(Error::Database(e))?,
+        Ok(_) => {}
+    }
+
+    if let Some(email) = email {
+        mail::queue(
+            &mut *transaction,
+            email,
+            &mail::INVITE,
+            &[
+                ("role", &role.to_string()),
+                ("hours", &(expires_in.as_secs() / 3600).to_string()),
+                ("link", &format!("{frontend_url}/login.html?invite={code}")),
+            ],
+        )
+        .await?;
     }
+
+    transaction.commit().await?;
+
+    Ok(code)
 }
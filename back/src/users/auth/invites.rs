@@ -0,0 +1,30 @@
+use crate::users::Role;
+use serde::Serialize;
+
+#[derive(utoipa::OpenApi)]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(get::openapi());
+    api.merge(delete::openapi());
+    api
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = invites::Response)]
+struct Response {
+    id: u64,
+    code: String,
+    role: Role,
+    email: Option<String>,
+    max_uses: u32,
+    uses: u32,
+    invited_by: Option<u64>,
+    used_by: Vec<u64>,
+    created_at: String,
+    expires_at: String,
+}
+
+pub mod delete;
+pub mod get;
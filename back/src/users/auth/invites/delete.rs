@@ -0,0 +1,58 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    permissions::{Require, require},
+    users::auth,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use axum_extra::extract::WithRejection as Rejectable;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(invite))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/auth/invites/{id}",
+    security(("session" = ["users.invite"])),
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Invite not found",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    )
+)]
+pub async fn invite(
+    State(AppState { pool, .. }): State<AppState>,
+    _: Require<require::UsersInvite>,
+    Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    Ok(sqlx::query("DELETE FROM invites WHERE id = ? LIMIT 1")
+        .bind(id)
+        .execute(&pool)
+        .await
+        .map_err(auth::invite::Error::Database)?
+        .rows_affected()
+        .ne(&0)
+        .then_some(StatusCode::NO_CONTENT)
+        .ok_or(auth::invite::Error::NotFound)?)
+}
@@ -0,0 +1,101 @@
+use crate::{
+    ApiResult, AppState,
+    permissions::{Require, require},
+    users::{
+        Role,
+        auth::{invite, invites::Response},
+    },
+};
+use axum::{Json, extract::State, response::IntoResponse};
+use time::OffsetDateTime;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(invites))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/auth/invites",
+    security(("session" = ["users.invite"])),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Response>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn invites(
+    State(AppState { pool, .. }): State<AppState>,
+    _: Require<require::UsersInvite>,
+) -> ApiResult<impl IntoResponse> {
+    let redemptions: Vec<(u64, u64)> =
+        sqlx::query_as("SELECT invite_id, id FROM accounts WHERE invite_id IS NOT NULL")
+            .fetch_all(&pool)
+            .await
+            .map_err(invite::Error::Database)?;
+
+    Ok(Json(
+        sqlx::query_as::<
+            _,
+            (
+                u64,
+                String,
+                Role,
+                Option<String>,
+                u32,
+                u32,
+                Option<u64>,
+                OffsetDateTime,
+                OffsetDateTime,
+            ),
+        >(
+            "SELECT id, code, role, email, max_uses, uses, invited_by, created_at, expires_at
+                FROM invites
+                ORDER BY created_at DESC",
+        )
+        .fetch_all(&pool)
+        .await
+        .map_err(invite::Error::Database)?
+        .into_iter()
+        .map(
+            |(id, code, role, email, max_uses, uses, invited_by, created_at, expires_at)| {
+                Ok(Response {
+                    id,
+                    code,
+                    role,
+                    email,
+                    max_uses,
+                    uses,
+                    invited_by,
+                    used_by: redemptions
+                        .iter()
+                        .filter(|(invite_id, _)| *invite_id == id)
+                        .map(|(_, account_id)| *account_id)
+                        .collect(),
+                    created_at: created_at
+                        .to_utc()
+                        .format(&time::format_description::well_known::Rfc3339)
+                        .map_err(invite::Error::TimeFormat)?,
+                    expires_at: expires_at
+                        .to_utc()
+                        .format(&time::format_description::well_known::Rfc3339)
+                        .map_err(invite::Error::TimeFormat)?,
+                })
+            },
+        )
+        .collect::<ApiResult<Vec<_>>>()?,
+    ))
+}
@@ -0,0 +1,159 @@
+use crate::{
+    ApiError, ApiResult, AppState, ErrorResponse, mail,
+    users::{
+        self,
+        auth::{TokenHasher, generate_token},
+        email::EmailAddress,
+    },
+};
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use std::time::Duration;
+
+const MAGIC_LINK_TOKEN_MAX_AGE: Duration = Duration::from_mins(15);
+// Links sent per account within `MAGIC_LINK_TOKEN_MAX_AGE`
+const MAGIC_LINK_MAX_REQUESTS: u32 = 3;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(magic_link))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(redeem::openapi());
+    api
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = magic_link::Request)]
+pub struct Request {
+    email: EmailAddress,
+    /// Keep the session alive for longer
+    #[serde(default)]
+    remember_me: bool,
+}
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "MAGIC_LINK_")]
+pub enum Error {
+    #[error("Signin link expired, used or not found")]
+    InvalidToken,
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/auth/magic-link",
+    description = "Email a single-use signin link to a verified email, accepted whether or not the account exists, \
+        has disabled signin links or has requested too many recently",
+    responses(
+        (
+            status = StatusCode::ACCEPTED,
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid email",
+        ),
+    ),
+)]
+pub async fn magic_link(
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        ..
+    }): State<AppState>,
+    Rejectable(Json(Request { email, remember_me }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    // Respond before looking anything up, so neither status nor timing reveal whether the account exists
+    tokio::spawn(async move {
+        if let Err(e) = request_link(&pool, &token_hasher, &email, remember_me, &frontend_url).await
+        {
+            eprintln!("Failed to request signin link: {e}");
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn request_link(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    email: &EmailAddress,
+    remember_me: bool,
+    frontend_url: &str,
+) -> Result<(), users::Error> {
+    // Only verified emails are known to belong to the account
+    let Some((account_id, recent_requests)): Option<(u64, i64)> = sqlx::query_as(
+        "SELECT accounts.id, COUNT(magic_links.id)
+            FROM accounts
+            LEFT JOIN magic_links ON magic_links.account_id = accounts.id
+                AND magic_links.created_at > NOW() - INTERVAL ? SECOND
+            WHERE accounts.email = ? AND accounts.email_verified_at IS NOT NULL
+                AND accounts.magic_link_enabled
+            GROUP BY accounts.id
+            LIMIT 1",
+    )
+    .bind(MAGIC_LINK_TOKEN_MAX_AGE.as_secs())
+    .bind(email)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(());
+    };
+
+    if recent_requests >= i64::from(MAGIC_LINK_MAX_REQUESTS) {
+        return Ok(());
+    }
+
+    let token = generate_token();
+
+    let _ = sqlx::query(
+        "INSERT INTO magic_links (token, account_id, remember_me, expires_at)
+            VALUES (?, ?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(token_hasher.hash(&token))
+    .bind(account_id)
+    .bind(remember_me)
+    .bind(MAGIC_LINK_TOKEN_MAX_AGE.as_secs())
+    .execute(pool)
+    .await?;
+
+    mail::queue(
+        pool,
+        email,
+        &mail::MAGIC_LINK,
+        &[(
+            "link",
+            &format!("{frontend_url}/login.html?magic-link={token}"),
+        )],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub mod redeem;
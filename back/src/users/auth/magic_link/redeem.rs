@@ -0,0 +1,113 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::auth::{
+        Client, create_session,
+        magic_link::Error,
+        session_cookie,
+        signin::{ChallengeResponse, reject_suspended, second_factors, totp::create_challenge},
+    },
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{StatusCode, header},
+    response::{AppendHeaders, IntoResponse, Response},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(redeem))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = magic_link::redeem::Request)]
+pub struct Request {
+    token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/auth/magic-link/redeem",
+    description = "Sign in with an emailed signin link",
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Successful signin"
+        ),
+        (
+            status = StatusCode::ACCEPTED,
+            description = "Link valid, second factor required",
+            body = ChallengeResponse,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Signin link expired, used or not found",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Account suspended",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR),
+    ),
+)]
+pub async fn redeem(
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
+    client: Client,
+    Rejectable(Json(Request { token }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<Response> {
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    // Links sent before the account disabled them are rejected as well
+    let (link_id, account_id, remember_me): (u64, u64, bool) = sqlx::query_as(
+        "SELECT magic_links.id, magic_links.account_id, magic_links.remember_me
+            FROM magic_links JOIN accounts ON accounts.id = magic_links.account_id
+            WHERE magic_links.token = ? AND magic_links.used_at IS NULL
+                AND magic_links.expires_at > NOW() AND accounts.magic_link_enabled
+            LIMIT 1
+            FOR UPDATE",
+    )
+    .bind(token_hasher.hash(&token))
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(Error::Database)?
+    .ok_or(Error::InvalidToken)?;
+
+    // Kept until expiry to count towards the rate limit
+    let _ = sqlx::query("UPDATE magic_links SET used_at = NOW() WHERE id = ? LIMIT 1")
+        .bind(link_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(Error::Database)?;
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    reject_suspended(&pool, account_id).await?;
+
+    let methods = second_factors(&pool, account_id)
+        .await
+        .map_err(Error::Database)?;
+    if !methods.is_empty() {
+        let challenge = create_challenge(&pool, &token_hasher, account_id, remember_me)
+            .await
+            .map_err(Error::Database)?;
+
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(ChallengeResponse { challenge, methods }),
+        )
+            .into_response());
+    }
+
+    let (token, max_age) = create_session(&pool, &token_hasher, account_id, remember_me, &client)
+        .await
+        .map_err(Error::Database)?;
+
+    Ok(AppendHeaders([(header::SET_COOKIE, session_cookie(&token, max_age))]).into_response())
+}
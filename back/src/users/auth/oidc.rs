@@ -0,0 +1,230 @@
+use crate::{ErrorResponse, users::auth::signup};
+use axum::{
+    Json,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use openidconnect::{
+    ClientId, ClientSecret, EndpointMaybeSet, EndpointNotSet, EndpointSet, IssuerUrl, RedirectUrl,
+    core::{CoreClient, CoreProviderMetadata},
+    reqwest,
+};
+use std::{
+    collections::HashMap,
+    env, error,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+const STATE_MAX_AGE: Duration = Duration::from_mins(10);
+// Providers rotate keys rarely, and signins with unknown keys discover them again
+const METADATA_MAX_AGE: Duration = Duration::from_hours(1);
+
+#[derive(utoipa::OpenApi)]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(start::openapi());
+    api.merge(callback::openapi());
+    api
+}
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "OIDC_")]
+pub enum Error {
+    #[error("Provider not configured")]
+    ProviderNotFound,
+    #[error("Signin attempt expired or not found")]
+    InvalidState,
+    #[error("Provider denied the signin: {0}")]
+    Denied(String),
+    #[error("Identity is not linked to an account, sign up with an invite first")]
+    AccountNotFound,
+    #[error("Provider did not return a valid email")]
+    MissingEmail,
+    #[error("Provider did not return an ID token")]
+    MissingIdToken,
+    #[error("Could not communicate with provider")]
+    Provider(#[source] Box<dyn error::Error + Send + Sync>),
+    #[error("Could not sign up: {0}")]
+    Signup(#[from] signup::Error),
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::ProviderNotFound => StatusCode::NOT_FOUND,
+            Self::InvalidState => StatusCode::UNAUTHORIZED,
+            Self::Denied(_) => StatusCode::UNAUTHORIZED,
+            Self::AccountNotFound => StatusCode::NOT_FOUND,
+            Self::MissingEmail => StatusCode::BAD_GATEWAY,
+            Self::MissingIdToken => StatusCode::BAD_GATEWAY,
+            Self::Provider(_) => StatusCode::BAD_GATEWAY,
+            Self::Signup(e) => return e.into_response(),
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+impl Error {
+    fn provider(e: impl error::Error + Send + Sync + 'static) -> Self {
+        Self::Provider(Box::new(e))
+    }
+}
+
+struct Provider {
+    issuer: IssuerUrl,
+    client_id: ClientId,
+    client_secret: Option<ClientSecret>,
+    redirect_url: RedirectUrl,
+    /// Endpoints and keys from the last discovery, and when it happened
+    metadata: RwLock<Option<(Instant, CoreProviderMetadata)>>,
+}
+
+/// OpenID Connect providers accounts can sign in with
+pub struct Providers {
+    http_client: reqwest::Client,
+    providers: HashMap<String, Provider>,
+}
+
+impl Providers {
+    /// Reads the whitespace separated names in `OIDC_PROVIDERS`, each configured by
+    /// `OIDC_<NAME>_ISSUER`, `OIDC_<NAME>_CLIENT_ID` and optionally `OIDC_<NAME>_CLIENT_SECRET`,
+    /// with callbacks below `API_URL`
+    pub fn from_env() -> Self {
+        let names = env::var("OIDC_PROVIDERS").unwrap_or_default();
+        let api_url = if let Ok(v) = env::var("API_URL") {
+            v.trim_end_matches('/').to_owned()
+        } else {
+            #[cfg(not(debug_assertions))]
+            assert!(names.trim().is_empty(), "API_URL must be set");
+            format!("http://localhost:{}", env::var("PORT").unwrap_or_default())
+        };
+
+        let providers = names
+            .split_whitespace()
+            .map(|name| {
+                let var = |key: &str| {
+                    env::var(format!(
+                        "OIDC_{}_{key}",
+                        name.to_uppercase().replace('-', "_")
+                    ))
+                };
+                let provider = Provider {
+                    issuer: IssuerUrl::new(
+                        var("ISSUER").unwrap_or_else(|_| panic!("OIDC_{name}_ISSUER must be set")),
+                    )
+                    .unwrap_or_else(|_| panic!("Invalid OIDC_{name}_ISSUER")),
+                    client_id: ClientId::new(
+                        var("CLIENT_ID")
+                            .unwrap_or_else(|_| panic!("OIDC_{name}_CLIENT_ID must be set")),
+                    ),
+                    client_secret: var("CLIENT_SECRET").ok().map(ClientSecret::new),
+                    redirect_url: RedirectUrl::new(format!(
+                        "{api_url}/users/auth/oidc/{name}/callback"
+                    ))
+                    .unwrap_or_else(|_| panic!("Invalid API_URL")),
+                    metadata: RwLock::default(),
+                };
+                (name.to_owned(), provider)
+            })
+            .collect();
+
+        Self {
+            http_client: reqwest::Client::builder()
+                // Following redirects would allow server-side request forgery
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("Unable to build http client"),
+            providers,
+        }
+    }
+
+    /// Client with the endpoints and keys of a provider, discovered at most once per `METADATA_MAX_AGE`
+    async fn client(
+        &self,
+        name: &str,
+    ) -> Result<
+        CoreClient<
+            EndpointSet,
+            EndpointNotSet,
+            EndpointNotSet,
+            EndpointNotSet,
+            EndpointMaybeSet,
+            EndpointMaybeSet,
+        >,
+        Error,
+    > {
+        let Provider {
+            issuer,
+            client_id,
+            client_secret,
+            redirect_url,
+            metadata: cache,
+        } = self.providers.get(name).ok_or(Error::ProviderNotFound)?;
+
+        let cached = cache
+            .read()
+            .await
+            .as_ref()
+            .filter(|(discovered_at, _)| discovered_at.elapsed() < METADATA_MAX_AGE)
+            .map(|(_, metadata)| metadata.clone());
+        let metadata = if let Some(metadata) = cached {
+            metadata
+        } else {
+            let metadata = CoreProviderMetadata::discover_async(issuer.clone(), &self.http_client)
+                .await
+                .map_err(Error::provider)?;
+            *cache.write().await = Some((Instant::now(), metadata.clone()));
+            metadata
+        };
+
+        Ok(
+            CoreClient::from_provider_metadata(metadata, client_id.clone(), client_secret.clone())
+                .set_redirect_uri(redirect_url.clone()),
+        )
+    }
+
+    /// Discards the discovered metadata of a provider, e.g. after its keys did not verify a token
+    async fn forget(&self, name: &str) {
+        if let Some(provider) = self.providers.get(name) {
+            *provider.metadata.write().await = None;
+        }
+    }
+}
+
+/// Binds a signin attempt to the browser that started it, preventing login CSRF
+fn state_cookie(state: &str, max_age: Duration) -> String {
+    #[cfg(debug_assertions)]
+    let cookie = format!(
+        "oidc_state={state}; Max-Age={}; Path=/users/auth/oidc; HttpOnly",
+        max_age.as_secs()
+    );
+    #[cfg(not(debug_assertions))]
+    let cookie = format!(
+        "oidc_state={state}; Max-Age={}; Path=/users/auth/oidc; HttpOnly; Secure; SameSite=None",
+        max_age.as_secs()
+    );
+    cookie
+}
+
+fn extract_state_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|s| s.trim().strip_prefix("oidc_state="))
+}
+
+pub mod callback;
+pub mod start;
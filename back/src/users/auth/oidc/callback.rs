@@ -0,0 +1,306 @@
+use crate::{
+    ApiError, ApiResult, AppState, mail,
+    users::{
+        auth::{
+            Client, create_session, generate_token,
+            oidc::{Error, Providers, extract_state_cookie, state_cookie},
+            session_cookie,
+            signin::{reject_suspended, second_factors, totp::create_challenge},
+            signup::{create_account, redeem_invite},
+            verify_email::send_verification,
+        },
+        email::EmailAddress,
+    },
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, header},
+    response::{AppendHeaders, IntoResponse, Redirect},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use openidconnect::{AuthorizationCode, Nonce, PkceCodeVerifier, TokenResponse};
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(callback))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/auth/oidc/{provider}/callback",
+    description = "Complete a signin at an OpenID Connect provider. \
+        Identities are linked to the account with the same email if the provider verified it, \
+        otherwise an account is created if an invite was passed to `start`.",
+    params(Params),
+    responses(
+        (
+            status = StatusCode::SEE_OTHER,
+            description = "Successful signin, redirect to the frontend, \
+                with a `signin-challenge` and its comma separated `methods` if a second factor is required",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Signin attempt expired, started in another browser or denied by the provider",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Provider not configured, or identity not linked and no invite given",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Account suspended",
+        ),
+        (
+            status = StatusCode::BAD_GATEWAY,
+            description = "Could not communicate with the provider",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR),
+    ),
+)]
+pub async fn callback(
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        password_hasher,
+        oidc_providers,
+        ..
+    }): State<AppState>,
+    client: Client,
+    headers: HeaderMap,
+    Rejectable(Path(provider), _): Rejectable<Path<String>, ApiError>,
+    Rejectable(
+        Query(Params {
+            code,
+            state,
+            error,
+            error_description,
+        }),
+        _,
+    ): Rejectable<Query<Params>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    if let Some(error) = error {
+        Err(Error::Denied(error_description.unwrap_or(error)))?
+    }
+    let (Some(code), Some(state)) = (code, state) else {
+        Err(Error::InvalidState)?
+    };
+    if extract_state_cookie(&headers) != Some(state.as_str()) {
+        Err(Error::InvalidState)?
+    }
+
+    let (state_id, pkce_verifier, nonce, invite, remember_me): (
+        u64,
+        String,
+        String,
+        Option<String>,
+        bool,
+    ) = sqlx::query_as(
+        "SELECT id, pkce_verifier, nonce, invite, remember_me
+            FROM oidc_states
+            WHERE state = ? AND provider = ? AND expires_at > NOW()
+            LIMIT 1",
+    )
+    .bind(token_hasher.hash(&state))
+    .bind(&provider)
+    .fetch_optional(&pool)
+    .await
+    .map_err(Error::Database)?
+    .ok_or(Error::InvalidState)?;
+
+    // Only the callback deleting the state may use it
+    let consumed = sqlx::query("DELETE FROM oidc_states WHERE id = ? LIMIT 1")
+        .bind(state_id)
+        .execute(&pool)
+        .await
+        .map_err(Error::Database)?
+        .rows_affected();
+    if consumed == 0 {
+        Err(Error::InvalidState)?
+    }
+
+    // The provider is asked before the transaction, which would otherwise hold locks meanwhile
+    let Identity {
+        subject,
+        email,
+        email_verified,
+    } = exchange(&oidc_providers, &provider, code, pkce_verifier, nonce).await?;
+
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    let linked: Option<u64> = sqlx::query_scalar(
+        "SELECT account_id FROM oidc_identities WHERE provider = ? AND subject = ? LIMIT 1",
+    )
+    .bind(&provider)
+    .bind(&subject)
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(Error::Database)?;
+
+    let account_id = if let Some(account_id) = linked {
+        reject_suspended(&mut *transaction, account_id).await?;
+        account_id
+    } else {
+        let existing: Option<u64> = match &email {
+            Some(email) if email_verified => sqlx::query_scalar(
+                "SELECT id FROM accounts
+                    WHERE email = ? AND email_verified_at IS NOT NULL
+                    LIMIT 1",
+            )
+            .bind(email)
+            .fetch_optional(&mut *transaction)
+            .await
+            .map_err(Error::Database)?,
+            _ => None,
+        };
+
+        let account_id = match (existing, invite) {
+            (Some(account_id), _) => {
+                // Not linked to a suspended account either
+                reject_suspended(&mut *transaction, account_id).await?;
+                account_id
+            }
+            (None, Some(invite)) => {
+                let email = email.ok_or(Error::MissingEmail)?;
+                let (invite_id, member, email_bound) =
+                    redeem_invite(&mut transaction, &invite, &email).await?;
+                // Signin happens via the provider, a password can be set via a reset
+                let account_id = create_account(
+                    &mut *transaction,
+                    &password_hasher,
+                    &email,
+                    &generate_token(),
+                    member,
+                    Some(invite_id),
+                    email_bound || email_verified,
+                )
+                .await?;
+
+                if !(email_bound || email_verified) {
+                    send_verification(
+                        &mut transaction,
+                        &token_hasher,
+                        account_id,
+                        &email,
+                        &mail::VERIFY_EMAIL,
+                        &frontend_url,
+                    )
+                    .await
+                    .map_err(|e| Error::Signup(e.into()))?;
+                }
+
+                account_id
+            }
+            (None, None) => Err(Error::AccountNotFound)?,
+        };
+
+        let _ = sqlx::query(
+            "INSERT INTO oidc_identities (account_id, provider, subject) VALUES (?, ?, ?)",
+        )
+        .bind(account_id)
+        .bind(&provider)
+        .bind(&subject)
+        .execute(&mut *transaction)
+        .await
+        .map_err(Error::Database)?;
+
+        account_id
+    };
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    let methods = second_factors(&pool, account_id)
+        .await
+        .map_err(Error::Database)?;
+
+    let mut cookies = vec![(header::SET_COOKIE, state_cookie("", Duration::ZERO))];
+
+    if !methods.is_empty() {
+        let challenge = create_challenge(&pool, &token_hasher, account_id, remember_me)
+            .await
+            .map_err(Error::Database)?;
+
+        return Ok((
+            AppendHeaders(cookies),
+            Redirect::to(&format!(
+                "{frontend_url}/login.html?signin-challenge={challenge}&methods={}",
+                methods
+                    .iter()
+                    .map(AsRef::as_ref)
+                    .collect::<Vec<&str>>()
+                    .join(",")
+            )),
+        ));
+    }
+
+    let (token, max_age) = create_session(&pool, &token_hasher, account_id, remember_me, &client)
+        .await
+        .map_err(Error::Database)?;
+
+    cookies.push((header::SET_COOKIE, session_cookie(&token, max_age)));
+
+    Ok((
+        AppendHeaders(cookies),
+        Redirect::to(&format!("{frontend_url}/")),
+    ))
+}
+
+/// Identity a provider vouched for in its ID token
+struct Identity {
+    subject: String,
+    email: Option<EmailAddress>,
+    email_verified: bool,
+}
+
+/// Redeems the authorization code at the provider and verifies the ID token it returns
+async fn exchange(
+    providers: &Providers,
+    provider: &str,
+    code: String,
+    pkce_verifier: String,
+    nonce: String,
+) -> Result<Identity, Error> {
+    let client = providers.client(provider).await?;
+    let response = client
+        .exchange_code(AuthorizationCode::new(code))
+        .map_err(Error::provider)?
+        .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier))
+        .request_async(&providers.http_client)
+        .await
+        .map_err(Error::provider)?;
+    let claims = match response
+        .id_token()
+        .ok_or(Error::MissingIdToken)?
+        .claims(&client.id_token_verifier(), &Nonce::new(nonce))
+    {
+        Ok(claims) => claims,
+        Err(e) => {
+            // The provider may have rotated its keys since they were discovered
+            providers.forget(provider).await;
+            Err(Error::provider(e))?
+        }
+    };
+
+    Ok(Identity {
+        subject: claims.subject().to_string(),
+        email: claims.email().and_then(|v| v.as_str().parse().ok()),
+        email_verified: claims.email_verified() == Some(true),
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,411 @@
+use super::*;
+use crate::{
+    tests::{self, FRONTEND_URL},
+    users::{Assignment, Role, Roles, auth::oidc::Provider},
+};
+use axum::{Json, Router, http::StatusCode, response::Response, routing};
+use openidconnect::{
+    ClientId, IssuerUrl, JsonWebKeyId, PrivateSigningKey, RedirectUrl,
+    core::{CoreIdToken, CoreIdTokenClaims, CoreJwsSigningAlgorithm, CoreRsaPrivateSigningKey},
+    reqwest,
+};
+use serde_json::{Value, json};
+use sqlx::MySqlPool;
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+use time::OffsetDateTime;
+use tokio::sync::RwLock;
+
+const CLIENT_ID: &str = "api";
+const STATE: &str = "state";
+const NONCE: &str = "nonce";
+
+/// Identity provider serving discovery, its keys and a token endpoint
+/// which returns an ID token with the claims set last
+struct Issuer {
+    url: String,
+    claims: Arc<Mutex<Value>>,
+    discoveries: Arc<AtomicUsize>,
+}
+
+impl Issuer {
+    async fn spawn() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Unable to bind mock issuer");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let claims = Arc::new(Mutex::new(Value::Null));
+        let discoveries = Arc::new(AtomicUsize::new(0));
+        let key = Arc::new(
+            CoreRsaPrivateSigningKey::from_pem(
+                include_str!("test_key.pem"),
+                Some(JsonWebKeyId::new("test".to_owned())),
+            )
+            .expect("Test key is valid"),
+        );
+
+        let discovery = json!({
+            "issuer": url,
+            "authorization_endpoint": format!("{url}/authorize"),
+            "token_endpoint": format!("{url}/token"),
+            "jwks_uri": format!("{url}/jwks"),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["RS256"],
+        });
+        let jwks = json!({ "keys": [key.as_verification_key()] });
+        let token = {
+            let (url, claims) = (url.clone(), claims.clone());
+            move || async move {
+                let now = OffsetDateTime::now_utc().unix_timestamp();
+                let mut claims = claims.lock().unwrap().clone();
+                claims["iss"] = json!(url);
+                claims["aud"] = json!([CLIENT_ID]);
+                claims["iat"] = json!(now);
+                claims["exp"] = json!(now + 60);
+                let claims: CoreIdTokenClaims =
+                    serde_json::from_value(claims).expect("Claims are valid");
+                let id_token = CoreIdToken::new(
+                    claims,
+                    key.as_ref(),
+                    CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256,
+                    None,
+                    None,
+                )
+                .expect("Unable to sign ID token");
+
+                Json(json!({
+                    "access_token": "access",
+                    "token_type": "Bearer",
+                    "expires_in": 60,
+                    "id_token": id_token.to_string(),
+                }))
+            }
+        };
+
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                routing::get({
+                    let discoveries = discoveries.clone();
+                    move || async move {
+                        discoveries.fetch_add(1, Ordering::Relaxed);
+                        Json(discovery)
+                    }
+                }),
+            )
+            .route("/jwks", routing::get(move || async move { Json(jwks) }))
+            .route("/token", routing::post(token));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        Self {
+            url,
+            claims,
+            discoveries,
+        }
+    }
+
+    fn respond_with(&self, claims: Value) {
+        *self.claims.lock().unwrap() = claims;
+    }
+
+    fn providers(&self) -> Providers {
+        Providers {
+            http_client: reqwest::Client::builder()
+                .no_proxy()
+                .build()
+                .expect("Unable to build http client"),
+            providers: HashMap::from([(
+                "mock".to_owned(),
+                Provider {
+                    issuer: IssuerUrl::new(self.url.clone()).unwrap(),
+                    client_id: ClientId::new(CLIENT_ID.to_owned()),
+                    client_secret: None,
+                    redirect_url: RedirectUrl::new(
+                        "http://api.test/users/auth/oidc/mock/callback".to_owned(),
+                    )
+                    .unwrap(),
+                    metadata: RwLock::default(),
+                },
+            )]),
+        }
+    }
+}
+
+async fn call_callback(state: AppState, cookie: Option<&str>, query_state: &str) -> Response {
+    let mut headers = HeaderMap::new();
+    if let Some(cookie) = cookie {
+        headers.insert(
+            header::COOKIE,
+            format!("oidc_state={cookie}").parse().unwrap(),
+        );
+    }
+
+    callback(
+        State(state),
+        Client {
+            ip: None,
+            user_agent: None,
+        },
+        headers,
+        Rejectable(Path("mock".to_owned()), PhantomData),
+        Rejectable(
+            Query(Params {
+                code: Some("code".to_owned()),
+                state: Some(query_state.to_owned()),
+                error: None,
+                error_description: None,
+            }),
+            PhantomData,
+        ),
+    )
+    .await
+    .into_response()
+}
+
+/// Stores a signin attempt as `start` would, expiring after `expires_in` seconds
+async fn insert_state(pool: &MySqlPool, state: &AppState, invite: Option<&str>, expires_in: i64) {
+    let _ = sqlx::query(
+        "INSERT INTO oidc_states
+            (state, provider, pkce_verifier, nonce, invite, remember_me, expires_at)
+            VALUES (?, 'mock', 'verifier', ?, ?, FALSE, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(state.token_hasher.hash(STATE))
+    .bind(NONCE)
+    .bind(invite)
+    .bind(expires_in)
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+async fn linked_account(pool: &MySqlPool, subject: &str) -> Option<u64> {
+    sqlx::query_scalar(
+        "SELECT account_id FROM oidc_identities WHERE provider = 'mock' AND subject = ? LIMIT 1",
+    )
+    .bind(subject)
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+fn location(response: &Response) -> &str {
+    response.headers()[header::LOCATION].to_str().unwrap()
+}
+
+#[tokio::test]
+async fn rejects_state_of_another_browser() {
+    let state = tests::app_state(tests::unconnected_pool());
+
+    let response = call_callback(state.clone(), Some("other"), STATE).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(tests::error_code(response).await, "OIDC_INVALID_STATE");
+
+    let response = call_callback(state, None, STATE).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(tests::error_code(response).await, "OIDC_INVALID_STATE");
+}
+
+#[tokio::test]
+async fn verifies_nonce_of_id_token() {
+    let issuer = Issuer::spawn().await;
+    let providers = issuer.providers();
+
+    issuer.respond_with(json!({ "sub": "subject", "nonce": "other" }));
+    let result = exchange(
+        &providers,
+        "mock",
+        "code".into(),
+        "verifier".into(),
+        NONCE.into(),
+    )
+    .await;
+    assert!(matches!(result, Err(Error::Provider(_))));
+
+    issuer.respond_with(json!({ "sub": "subject" }));
+    let result = exchange(
+        &providers,
+        "mock",
+        "code".into(),
+        "verifier".into(),
+        NONCE.into(),
+    )
+    .await;
+    assert!(matches!(result, Err(Error::Provider(_))));
+
+    issuer.respond_with(json!({
+        "sub": "subject",
+        "nonce": NONCE,
+        "email": "Someone@example.org",
+        "email_verified": true,
+    }));
+    let Identity {
+        subject,
+        email,
+        email_verified,
+    } = exchange(
+        &providers,
+        "mock",
+        "code".into(),
+        "verifier".into(),
+        NONCE.into(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(subject, "subject");
+    assert_eq!(
+        email.map(|v| v.to_string()).as_deref(),
+        Some("Someone@example.org")
+    );
+    assert!(email_verified);
+}
+
+#[tokio::test]
+async fn caches_discovery_until_verification_fails() {
+    let issuer = Issuer::spawn().await;
+    let providers = issuer.providers();
+    let exchange = || {
+        exchange(
+            &providers,
+            "mock",
+            "code".into(),
+            "verifier".into(),
+            NONCE.into(),
+        )
+    };
+
+    issuer.respond_with(json!({ "sub": "subject", "nonce": NONCE }));
+    assert!(exchange().await.is_ok());
+    assert!(exchange().await.is_ok());
+    assert_eq!(issuer.discoveries.load(Ordering::Relaxed), 1);
+
+    issuer.respond_with(json!({ "sub": "subject", "nonce": "other" }));
+    assert!(exchange().await.is_err());
+    issuer.respond_with(json!({ "sub": "subject", "nonce": NONCE }));
+    assert!(exchange().await.is_ok());
+    assert_eq!(issuer.discoveries.load(Ordering::Relaxed), 2);
+}
+
+#[sqlx::test]
+#[ignore = "needs a MySQL server at DATABASE_URL"]
+async fn rejects_expired_or_used_state(pool: MySqlPool) {
+    let issuer = Issuer::spawn().await;
+    issuer.respond_with(json!({ "sub": "subject", "nonce": NONCE }));
+    let state = AppState {
+        oidc_providers: Arc::new(issuer.providers()),
+        ..tests::app_state(pool.clone())
+    };
+
+    insert_state(&pool, &state, None, -1).await;
+    let response = call_callback(state.clone(), Some(STATE), STATE).await;
+    assert_eq!(tests::error_code(response).await, "OIDC_INVALID_STATE");
+
+    let _ = sqlx::query("DELETE FROM oidc_states").execute(&pool).await;
+    insert_state(&pool, &state, None, 60).await;
+    let response = call_callback(state.clone(), Some(STATE), STATE).await;
+    assert_eq!(tests::error_code(response).await, "OIDC_ACCOUNT_NOT_FOUND");
+    let response = call_callback(state, Some(STATE), STATE).await;
+    assert_eq!(tests::error_code(response).await, "OIDC_INVALID_STATE");
+}
+
+#[sqlx::test]
+#[ignore = "needs a MySQL server at DATABASE_URL"]
+async fn links_account_by_verified_email(pool: MySqlPool) {
+    let issuer = Issuer::spawn().await;
+    let state = AppState {
+        oidc_providers: Arc::new(issuer.providers()),
+        ..tests::app_state(pool.clone())
+    };
+    let email: EmailAddress = "someone@example.org".parse().unwrap();
+    let account_id = create_account(
+        &pool,
+        &state.password_hasher,
+        &email,
+        "password",
+        Assignment {
+            role: Role::Editor,
+            co_op: None,
+        },
+        None,
+        true,
+    )
+    .await
+    .unwrap();
+
+    // An unverified email could belong to anyone
+    issuer.respond_with(json!({
+        "sub": "unverified",
+        "nonce": NONCE,
+        "email": email.to_string(),
+        "email_verified": false,
+    }));
+    insert_state(&pool, &state, None, 60).await;
+    let response = call_callback(state.clone(), Some(STATE), STATE).await;
+    assert_eq!(tests::error_code(response).await, "OIDC_ACCOUNT_NOT_FOUND");
+    assert_eq!(linked_account(&pool, "unverified").await, None);
+
+    issuer.respond_with(json!({
+        "sub": "verified",
+        "nonce": NONCE,
+        "email": email.to_string(),
+        "email_verified": true,
+    }));
+    insert_state(&pool, &state, None, 60).await;
+    let response = call_callback(state, Some(STATE), STATE).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(location(&response), format!("{FRONTEND_URL}/"));
+    assert_eq!(linked_account(&pool, "verified").await, Some(account_id));
+}
+
+#[sqlx::test]
+#[ignore = "needs a MySQL server at DATABASE_URL"]
+async fn signs_up_with_invite(pool: MySqlPool) {
+    let issuer = Issuer::spawn().await;
+    let state = AppState {
+        oidc_providers: Arc::new(issuer.providers()),
+        ..tests::app_state(pool.clone())
+    };
+    let _ = sqlx::query(
+        "INSERT INTO invites (role, code, expires_at)
+            VALUES ('editor', 'INVITE', NOW() + INTERVAL 1 HOUR)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    issuer.respond_with(json!({
+        "sub": "new",
+        "nonce": NONCE,
+        "email": "new@example.org",
+        "email_verified": true,
+    }));
+    insert_state(&pool, &state, Some("INVITE"), 60).await;
+    let response = call_callback(state, Some(STATE), STATE).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let (account_id, verified): (u64, bool) = sqlx::query_as(
+        "SELECT id, email_verified_at IS NOT NULL FROM accounts WHERE email = ? LIMIT 1",
+    )
+    .bind("new@example.org")
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        Roles::load(&pool, account_id).await.unwrap().in_co_op(None),
+        Some(Role::Editor)
+    );
+    assert!(verified);
+    assert_eq!(linked_account(&pool, "new").await, Some(account_id));
+
+    let uses: u32 = sqlx::query_scalar("SELECT uses FROM invites WHERE code = 'INVITE'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(uses, 1);
+}
@@ -0,0 +1,105 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::auth::oidc::{Error, STATE_MAX_AGE, state_cookie},
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{AppendHeaders, IntoResponse, Redirect},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use openidconnect::{CsrfToken, Nonce, PkceCodeChallenge, Scope, core::CoreAuthenticationFlow};
+use serde::Deserialize;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(start))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Params {
+    /// Sign up with this invite if the identity is not linked to an account yet
+    invite: Option<String>,
+    /// Keep the session alive for longer
+    #[serde(default)]
+    remember_me: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/auth/oidc/{provider}/start",
+    description = "Redirect to an OpenID Connect provider to sign in",
+    params(Params),
+    responses(
+        (
+            status = StatusCode::SEE_OTHER,
+            description = "Redirect to the provider, which redirects back to `/users/auth/oidc/{provider}/callback`",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Provider not configured",
+        ),
+        (
+            status = StatusCode::BAD_GATEWAY,
+            description = "Could not discover the provider",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR),
+    ),
+)]
+pub async fn start(
+    State(AppState {
+        pool,
+        token_hasher,
+        oidc_providers,
+        ..
+    }): State<AppState>,
+    Rejectable(Path(provider), _): Rejectable<Path<String>, ApiError>,
+    Rejectable(
+        Query(Params {
+            invite,
+            remember_me,
+        }),
+        _,
+    ): Rejectable<Query<Params>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    let client = oidc_providers.client(&provider).await?;
+
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let (url, state, nonce) = client
+        .authorize_url(
+            CoreAuthenticationFlow::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        )
+        .add_scope(Scope::new("email".to_owned()))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+
+    let _ = sqlx::query(
+        "INSERT INTO oidc_states
+            (state, provider, pkce_verifier, nonce, invite, remember_me, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(token_hasher.hash(state.secret()))
+    .bind(&provider)
+    .bind(pkce_verifier.secret())
+    .bind(nonce.secret())
+    .bind(invite)
+    .bind(remember_me)
+    .bind(STATE_MAX_AGE.as_secs())
+    .execute(&pool)
+    .await
+    .map_err(Error::Database)?;
+
+    Ok((
+        AppendHeaders([(
+            header::SET_COOKIE,
+            state_cookie(state.secret(), STATE_MAX_AGE),
+        )]),
+        Redirect::to(url.as_str()),
+    ))
+}
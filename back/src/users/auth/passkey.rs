@@ -0,0 +1,102 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        auth::signin::totp::CHALLENGE_MAX_ATTEMPTS,
+        passkey::{self, Error},
+        totp,
+    },
+};
+use axum::{Json, extract::State, response::IntoResponse};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::{Deserialize, Serialize};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(passkey))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(confirm::openapi());
+    api
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = passkey::Request)]
+pub struct Request {
+    /// Challenge of a password signin to complete with a passkey as second factor,
+    /// otherwise the passkey replaces the password
+    signin_challenge: Option<String>,
+}
+
+/// Options for `navigator.credentials.get()`, binary values are base64url encoded
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = passkey::RequestOptions)]
+struct RequestOptions {
+    challenge: String,
+    rp_id: String,
+    /// Credential ids of the account when used as second factor,
+    /// empty to let the user choose a passkey
+    allow_credentials: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/auth/passkey",
+    description = "Start signing in with a passkey, to be completed at `/users/auth/passkey/confirm`",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = RequestOptions,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Signin challenge expired or not found",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR),
+    ),
+)]
+pub async fn passkey(
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        ..
+    }): State<AppState>,
+    Rejectable(Json(Request { signin_challenge }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    let account_id = match signin_challenge {
+        Some(signin_challenge) => Some(
+            sqlx::query_scalar(
+                "SELECT account_id
+                    FROM signin_challenges
+                    WHERE token = ? AND expires_at > NOW() AND attempts < ?
+                    LIMIT 1",
+            )
+            .bind(token_hasher.hash(&signin_challenge))
+            .bind(CHALLENGE_MAX_ATTEMPTS)
+            .fetch_optional(&pool)
+            .await
+            .map_err(Error::Database)?
+            .ok_or(totp::Error::InvalidChallenge)?,
+        ),
+        None => None,
+    };
+
+    let allow_credentials = match account_id {
+        Some(account_id) => passkey::credential_ids(&pool, account_id)
+            .await
+            .map_err(Error::Database)?,
+        None => Vec::new(),
+    };
+    let challenge = passkey::create_challenge(&pool, &token_hasher, account_id)
+        .await
+        .map_err(Error::Database)?;
+
+    Ok(Json(RequestOptions {
+        challenge,
+        rp_id: passkey::rp_id(&frontend_url),
+        allow_credentials,
+    }))
+}
+
+pub mod confirm;
@@ -0,0 +1,170 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        auth::{
+            Client, create_session, session_cookie,
+            signin::{reject_suspended, totp::CHALLENGE_MAX_ATTEMPTS},
+        },
+        passkey::{self, Assertion, Error},
+        totp,
+    },
+};
+use axum::{
+    Json,
+    extract::State,
+    http::header,
+    response::{AppendHeaders, IntoResponse},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(confirm))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+/// Response of `navigator.credentials.get()`, binary values base64url encoded
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = passkey::confirm::Request)]
+pub struct Request {
+    credential_id: String,
+    client_data_json: String,
+    authenticator_data: String,
+    signature: String,
+    /// Challenge of the password signin the passkey is the second factor of
+    signin_challenge: Option<String>,
+    /// Keep the session alive for longer, taken from the password signin if any
+    #[serde(default)]
+    remember_me: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/auth/passkey/confirm",
+    description = "Complete signing in with a passkey, which has to verify the user unless it is a second factor",
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Successful signin"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Challenge expired or not found, or response invalid",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Passkey not found",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Account suspended",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR),
+    ),
+)]
+pub async fn confirm(
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        ..
+    }): State<AppState>,
+    client: Client,
+    Rejectable(
+        Json(Request {
+            credential_id,
+            client_data_json,
+            authenticator_data,
+            signature,
+            signin_challenge,
+            remember_me,
+        }),
+        _,
+    ): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    let credential_id = passkey::decode(&credential_id)?;
+    let client_data_json = passkey::decode(&client_data_json)?;
+    let authenticator_data = passkey::decode(&authenticator_data)?;
+    let signature = passkey::decode(&signature)?;
+    let assertion = Assertion {
+        credential_id: &credential_id,
+        client_data_json: &client_data_json,
+        authenticator_data: &authenticator_data,
+        signature: &signature,
+    };
+    let rp_id = passkey::rp_id(&frontend_url);
+
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    let (account_id, remember_me) = if let Some(signin_challenge) = signin_challenge {
+        let (challenge_id, account_id, remember_me): (u64, u64, bool) = sqlx::query_as(
+            "SELECT id, account_id, remember_me
+                FROM signin_challenges
+                WHERE token = ? AND expires_at > NOW() AND attempts < ?
+                LIMIT 1
+                FOR UPDATE",
+        )
+        .bind(token_hasher.hash(&signin_challenge))
+        .bind(CHALLENGE_MAX_ATTEMPTS)
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(Error::Database)?
+        .ok_or(totp::Error::InvalidChallenge)?;
+
+        let _ = sqlx::query(
+            "UPDATE signin_challenges SET attempts = attempts + 1 WHERE id = ? LIMIT 1",
+        )
+        .bind(challenge_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(Error::Database)?;
+
+        if let Err(e) = passkey::verify_assertion(
+            &mut transaction,
+            &token_hasher,
+            &rp_id,
+            Some(account_id),
+            assertion,
+        )
+        .await
+        {
+            // Keep the counted attempt
+            transaction.commit().await.map_err(Error::Database)?;
+            return Err(e.into());
+        }
+
+        let _ = sqlx::query("DELETE FROM signin_challenges WHERE id = ? LIMIT 1")
+            .bind(challenge_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(Error::Database)?;
+
+        (account_id, remember_me)
+    } else {
+        let (account_id, user_verified) =
+            passkey::verify_assertion(&mut transaction, &token_hasher, &rp_id, None, assertion)
+                .await?;
+        // Only a verified user makes the passkey a replacement for both factors
+        if !user_verified {
+            Err(Error::UserVerificationRequired)?
+        }
+
+        (account_id, remember_me)
+    };
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    reject_suspended(&pool, account_id).await?;
+
+    let (token, max_age) = create_session(&pool, &token_hasher, account_id, remember_me, &client)
+        .await
+        .map_err(Error::Database)?;
+
+    Ok(AppendHeaders([(
+        header::SET_COOKIE,
+        session_cookie(&token, max_age),
+    )]))
+}
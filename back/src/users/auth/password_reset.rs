@@ -0,0 +1,151 @@
+use crate::{
+    ApiError, ApiResult, AppState, ErrorResponse, mail,
+    users::{
+        self,
+        auth::{TokenHasher, generate_token},
+        email::EmailAddress,
+    },
+};
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use std::time::Duration;
+
+const PASSWORD_RESET_TOKEN_MAX_AGE: Duration = Duration::from_hours(1);
+// Links sent per account within `PASSWORD_RESET_TOKEN_MAX_AGE`
+const PASSWORD_RESET_MAX_REQUESTS: u32 = 3;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(password_reset))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(confirm::openapi());
+    api
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = password_reset::Request)]
+pub struct Request {
+    email: EmailAddress,
+}
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "PASSWORD_RESET_")]
+pub enum Error {
+    #[error("Reset token expired or not found")]
+    InvalidToken,
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/auth/password-reset",
+    description = "Email a password reset link to a verified email, accepted whether or not the account exists",
+    responses(
+        (
+            status = StatusCode::ACCEPTED,
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid email",
+        ),
+    ),
+)]
+pub async fn password_reset(
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        ..
+    }): State<AppState>,
+    Rejectable(Json(Request { email }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    // Respond before looking anything up, so neither status nor timing reveal whether the account exists
+    tokio::spawn(async move {
+        if let Err(e) = request_reset(&pool, &token_hasher, &email, &frontend_url).await {
+            eprintln!("Failed to request password reset: {e}");
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn request_reset(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    email: &EmailAddress,
+    frontend_url: &str,
+) -> Result<(), users::Error> {
+    // Only verified emails are known to belong to the account
+    let Some((account_id, recent_requests)): Option<(u64, i64)> = sqlx::query_as(
+        "SELECT accounts.id, COUNT(password_resets.id)
+            FROM accounts
+            LEFT JOIN password_resets ON password_resets.account_id = accounts.id
+                AND password_resets.created_at > NOW() - INTERVAL ? SECOND
+            WHERE accounts.email = ? AND accounts.email_verified_at IS NOT NULL
+            GROUP BY accounts.id
+            LIMIT 1",
+    )
+    .bind(PASSWORD_RESET_TOKEN_MAX_AGE.as_secs())
+    .bind(email)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(());
+    };
+
+    if recent_requests >= i64::from(PASSWORD_RESET_MAX_REQUESTS) {
+        return Ok(());
+    }
+
+    let token = generate_token();
+
+    let _ = sqlx::query(
+        "INSERT INTO password_resets (token, account_id, expires_at)
+            VALUES (?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(token_hasher.hash(&token))
+    .bind(account_id)
+    .bind(PASSWORD_RESET_TOKEN_MAX_AGE.as_secs())
+    .execute(pool)
+    .await?;
+
+    mail::queue(
+        pool,
+        email,
+        &mail::PASSWORD_RESET,
+        &[(
+            "link",
+            &format!("{frontend_url}/login.html?password-reset={token}"),
+        )],
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub mod confirm;
@@ -0,0 +1,89 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        self,
+        auth::{self, password_reset::Error},
+        email::EmailAddress,
+        password,
+    },
+};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(confirm))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = password_reset::confirm::Request)]
+pub struct Request {
+    token: String,
+    password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/auth/password-reset/confirm",
+    description = "Set a new password with an emailed reset token, signing out all sessions",
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Password changed",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Reset token expired or not found",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Password violates the password policy, the failed `rules` are listed",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR),
+    ),
+)]
+pub async fn confirm(
+    State(AppState {
+        pool,
+        token_hasher,
+        password_hasher,
+        breached_passwords,
+        ..
+    }): State<AppState>,
+    Rejectable(Json(Request { token, password }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    let (account_id, email): (u64, EmailAddress) = sqlx::query_as(
+        "SELECT password_resets.account_id, accounts.email
+            FROM password_resets JOIN accounts ON accounts.id = password_resets.account_id
+            WHERE password_resets.token = ? AND password_resets.expires_at > NOW()
+            LIMIT 1
+            FOR UPDATE",
+    )
+    .bind(token_hasher.hash(&token))
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(Error::Database)?
+    .ok_or(Error::InvalidToken)?;
+
+    password::check(&mut *transaction, &breached_passwords, &email, &password).await?;
+    users::set_password(&mut *transaction, &password_hasher, account_id, &password).await?;
+
+    let _ = sqlx::query("DELETE FROM password_resets WHERE account_id = ?")
+        .bind(account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(Error::Database)?;
+    let _ = auth::end_sessions(&mut *transaction, auth::Sessions::Account(account_id))
+        .await
+        .map_err(Error::Database)?;
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    Ok(StatusCode::OK)
+}
@@ -1,11 +1,12 @@
 use crate::{
-    ApiError, ApiResult, AppState, ErrorResponse,
+    ApiError, ApiResult, AppState, ErrorResponse, settings,
     users::{
-        auth::{SESSION_TOKEN_MAX_AGE, generate_session_token},
+        self,
+        auth::{Client, create_session, session_cookie},
         email::EmailAddress,
     },
 };
-use argon2::{Argon2, PasswordHash, PasswordVerifier, password_hash};
+use argon2::password_hash;
 use axum::{
     Json,
     extract::State,
@@ -13,7 +14,9 @@ use axum::{
     response::{AppendHeaders, IntoResponse, Response},
 };
 use axum_extra::extract::WithRejection as Rejectable;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlExecutor;
+use std::time::Duration;
 
 #[derive(utoipa::OpenApi)]
 #[openapi(paths(signin))]
@@ -28,6 +31,51 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 pub struct Request {
     email: EmailAddress,
     password: String,
+    /// Keep the session alive for longer
+    #[serde(default)]
+    remember_me: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = signin::ChallengeResponse)]
+pub struct ChallengeResponse {
+    /// Complete the signin with it at `/users/auth/signin/totp` or `/users/auth/passkey/confirm`
+    pub challenge: String,
+    pub methods: Vec<SecondFactor>,
+}
+
+#[derive(Serialize, utoipa::ToSchema, strum::AsRefStr)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+#[schema(as = signin::SecondFactor)]
+pub enum SecondFactor {
+    Totp,
+    Passkey,
+}
+
+/// Returns the second factors an account has set up
+pub async fn second_factors(
+    executor: impl MySqlExecutor<'_>,
+    account_id: u64,
+) -> sqlx::Result<Vec<SecondFactor>> {
+    let (totp, passkey): (bool, bool) = sqlx::query_as(
+        "SELECT totp_enabled_at IS NOT NULL,
+                EXISTS(SELECT 1 FROM passkeys WHERE passkeys.account_id = accounts.id)
+            FROM accounts
+            WHERE id = ?
+            LIMIT 1",
+    )
+    .bind(account_id)
+    .fetch_one(executor)
+    .await?;
+
+    Ok([
+        totp.then_some(SecondFactor::Totp),
+        passkey.then_some(SecondFactor::Passkey),
+    ]
+    .into_iter()
+    .flatten()
+    .collect())
 }
 
 #[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
@@ -38,6 +86,12 @@ pub enum Error {
     IncorrectPassword,
     #[error("Account not found")]
     AccountNotFound,
+    #[error("Email or password incorrect")]
+    InvalidCredentials,
+    #[error("Too many failed signins, try again later")]
+    Locked(Duration),
+    #[error("Account is suspended")]
+    Suspended,
     #[error("Could not hash password")]
     PasswordHash(#[from] password_hash::Error),
     #[error("Could not query database")]
@@ -49,14 +103,26 @@ impl IntoResponse for Error {
         let status = match self {
             Self::IncorrectPassword => StatusCode::UNAUTHORIZED,
             Self::AccountNotFound => StatusCode::NOT_FOUND,
+            Self::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            Self::Locked(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::Suspended => StatusCode::FORBIDDEN,
             Self::PasswordHash(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
+        let retry_after = match self {
+            Self::Locked(duration) => Some((header::RETRY_AFTER, duration.as_secs().to_string())),
+            _ => None,
+        };
 
         let error = self.as_ref().to_string();
         let message = self.to_string();
 
-        (status, Json(ErrorResponse { error, message })).into_response()
+        (
+            status,
+            AppendHeaders(retry_after),
+            Json(ErrorResponse { error, message }),
+        )
+            .into_response()
     }
 }
 
@@ -68,66 +134,145 @@ impl IntoResponse for Error {
             status = StatusCode::OK,
             description = "Successful signin"
         ),
+        (
+            status = StatusCode::ACCEPTED,
+            description = "Password correct, second factor required",
+            body = ChallengeResponse,
+        ),
         (
             status = StatusCode::UNPROCESSABLE_ENTITY,
             description = "Invalid email",
         ),
         (
             status = StatusCode::UNAUTHORIZED,
-            description = "Password incorrect",
+            description = "Password incorrect, or email or password incorrect with generic errors enabled",
         ),
         (
             status = StatusCode::NOT_FOUND,
             description = "Account not found",
         ),
+        (
+            status = StatusCode::TOO_MANY_REQUESTS,
+            description = "Locked after too many failed signins, see `Retry-After`",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Account suspended",
+        ),
         (status = StatusCode::INTERNAL_SERVER_ERROR),
     ),
 )]
 pub async fn signin(
-    State(AppState { pool }): State<AppState>,
-    Rejectable(Json(Request { email, password }), _): Rejectable<Json<Request>, ApiError>,
-) -> ApiResult<impl IntoResponse> {
-    let (id, hashed_password): (u64, String) =
-        sqlx::query_as("SELECT id, password FROM accounts WHERE email = ? LIMIT 1")
-            .bind(&email)
-            .fetch_optional(&pool)
+    State(AppState {
+        pool,
+        token_hasher,
+        password_hasher,
+        ..
+    }): State<AppState>,
+    client: Client,
+    Rejectable(
+        Json(Request {
+            email,
+            password,
+            remember_me,
+        }),
+        _,
+    ): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<Response> {
+    let settings::Settings {
+        signin_lockout_threshold,
+        signin_generic_errors,
+        ..
+    } = settings::load(&pool).await.map_err(Error::Database)?;
+
+    let account: Option<(u64, String, bool)> = sqlx::query_as(
+        "SELECT id, password, suspended_at IS NOT NULL FROM accounts WHERE email = ? LIMIT 1",
+    )
+    .bind(&email)
+    .fetch_optional(&pool)
+    .await
+    .map_err(Error::Database)?;
+
+    let ip = client.ip.as_deref();
+    if let Some(duration) = lockout::locked_for(&pool, &email, ip, signin_lockout_threshold)
+        .await
+        .map_err(Error::Database)?
+    {
+        Err(Error::Locked(duration))?
+    }
+
+    let Some((id, hashed_password, suspended)) = account else {
+        // Unknown emails take as long and lock out the same as wrong passwords,
+        // so with generic errors nothing tells them apart
+        password_hasher.verify_dummy(&password);
+        lockout::record_failure(&pool, &email, ip)
             .await
-            .map_err(Error::Database)?
-            .ok_or(Error::AccountNotFound)?;
+            .map_err(Error::Database)?;
+        Err(if signin_generic_errors {
+            Error::InvalidCredentials
+        } else {
+            Error::AccountNotFound
+        })?
+    };
 
-    Argon2::default()
-        .verify_password(
-            password.as_bytes(),
-            &PasswordHash::new(&hashed_password).map_err(Error::PasswordHash)?,
-        )
-        .map_err(|e| match e {
-            password_hash::Error::Password => Error::IncorrectPassword,
-            e => Error::PasswordHash(e),
-        })?;
+    if !password_hasher
+        .verify(&hashed_password, &password)
+        .map_err(Error::PasswordHash)?
+    {
+        lockout::record_failure(&pool, &email, ip)
+            .await
+            .map_err(Error::Database)?;
+        Err(if signin_generic_errors {
+            Error::InvalidCredentials
+        } else {
+            Error::IncorrectPassword
+        })?
+    }
+
+    let _ = lockout::clear_failures(&pool, &email)
+        .await
+        .map_err(Error::Database)?;
+    // Only revealed to whoever knows the password
+    if suspended {
+        Err(Error::Suspended)?
+    }
+    users::rehash_password(&pool, &password_hasher, id, &hashed_password, &password).await;
 
-    let token = generate_session_token();
+    let methods = second_factors(&pool, id).await.map_err(Error::Database)?;
+    if !methods.is_empty() {
+        let challenge = totp::create_challenge(&pool, &token_hasher, id, remember_me)
+            .await
+            .map_err(Error::Database)?;
 
-    let _ = sqlx::query(
-            "INSERT INTO sessions (token, account_id, expires_at) VALUES (?, ?, NOW() + INTERVAL ? SECOND)",
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(ChallengeResponse { challenge, methods }),
         )
-        .bind(&token)
-        .bind(id)
-        .bind(SESSION_TOKEN_MAX_AGE.as_secs())
-        .execute(&pool)
+            .into_response());
+    }
+
+    let (token, max_age) = create_session(&pool, &token_hasher, id, remember_me, &client)
         .await
         .map_err(Error::Database)?;
 
-    Ok(AppendHeaders([(
-        header::SET_COOKIE,
-        #[cfg(debug_assertions)]
-        format!(
-            "session_token={token}; Max-Age={}; Path=/; HttpOnly",
-            SESSION_TOKEN_MAX_AGE.as_secs()
-        ),
-        #[cfg(not(debug_assertions))]
-        format!(
-            "session_token={token}; Max-Age={}; Path=/; HttpOnly; Secure; SameSite=None",
-            SESSION_TOKEN_MAX_AGE.as_secs()
-        ),
-    )]))
+    Ok(AppendHeaders([(header::SET_COOKIE, session_cookie(&token, max_age))]).into_response())
 }
+
+/// Rejects signing in to a suspended account, once its credentials were verified
+pub async fn reject_suspended(
+    executor: impl MySqlExecutor<'_>,
+    account_id: u64,
+) -> Result<(), Error> {
+    let suspended: bool =
+        sqlx::query_scalar("SELECT suspended_at IS NOT NULL FROM accounts WHERE id = ? LIMIT 1")
+            .bind(account_id)
+            .fetch_one(executor)
+            .await?;
+    if suspended {
+        Err(Error::Suspended)?
+    }
+    Ok(())
+}
+
+pub mod lockout;
+pub mod totp;
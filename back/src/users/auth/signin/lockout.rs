@@ -0,0 +1,88 @@
+use crate::users::email::EmailAddress;
+use sqlx::{MySqlExecutor, MySqlPool};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+// Failures count for an hour, each one beyond the threshold doubles the lockout up to half an hour
+const SIGNIN_FAILURE_MAX_AGE: Duration = Duration::from_hours(1);
+const LOCKOUT_BASE_DURATION: Duration = Duration::from_mins(1);
+const LOCKOUT_MAX_DOUBLINGS: u32 = 5;
+// IP addresses may be shared by several people
+const IP_THRESHOLD_FACTOR: u32 = 4;
+
+/// Returns for how much longer signin is locked for the email or IP address,
+/// whether or not an account with the email exists
+pub async fn locked_for(
+    pool: &MySqlPool,
+    email: &EmailAddress,
+    ip: Option<&str>,
+    threshold: u32,
+) -> sqlx::Result<Option<Duration>> {
+    let (email_failures, email_last_failure): (u64, Option<OffsetDateTime>) = sqlx::query_as(
+        "SELECT CAST(COUNT(*) AS UNSIGNED), MAX(created_at)
+            FROM signin_failures
+            WHERE email = ? AND expires_at > NOW()",
+    )
+    .bind(email.normalized())
+    .fetch_one(pool)
+    .await?;
+    let (ip_failures, ip_last_failure): (u64, Option<OffsetDateTime>) = sqlx::query_as(
+        "SELECT CAST(COUNT(*) AS UNSIGNED), MAX(created_at)
+            FROM signin_failures
+            WHERE ip = ? AND expires_at > NOW()",
+    )
+    .bind(ip)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(
+        lockout(email_failures, email_last_failure, threshold).max(lockout(
+            ip_failures,
+            ip_last_failure,
+            threshold.saturating_mul(IP_THRESHOLD_FACTOR),
+        )),
+    )
+}
+
+fn lockout(
+    failures: u64,
+    last_failure: Option<OffsetDateTime>,
+    threshold: u32,
+) -> Option<Duration> {
+    let excess = failures.checked_sub(threshold.into())?;
+    let doublings = excess.min(LOCKOUT_MAX_DOUBLINGS.into()) as u32;
+    let locked_until = last_failure? + LOCKOUT_BASE_DURATION * 2u32.pow(doublings);
+    Duration::try_from(locked_until - OffsetDateTime::now_utc())
+        .ok()
+        .filter(|remaining| !remaining.is_zero())
+}
+
+pub async fn record_failure(
+    executor: impl MySqlExecutor<'_>,
+    email: &EmailAddress,
+    ip: Option<&str>,
+) -> sqlx::Result<()> {
+    let _ = sqlx::query(
+        "INSERT INTO signin_failures (email, ip, expires_at)
+            VALUES (?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(email.normalized())
+    .bind(ip)
+    .bind(SIGNIN_FAILURE_MAX_AGE.as_secs())
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Unlocks an email, failures of the IP addresses it was tried from still count
+pub async fn clear_failures(
+    executor: impl MySqlExecutor<'_>,
+    email: &EmailAddress,
+) -> sqlx::Result<u64> {
+    Ok(sqlx::query("DELETE FROM signin_failures WHERE email = ?")
+        .bind(email.normalized())
+        .execute(executor)
+        .await?
+        .rows_affected())
+}
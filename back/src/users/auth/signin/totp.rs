@@ -0,0 +1,138 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        auth::{
+            Client, TokenHasher, create_session, generate_token, session_cookie,
+            signin::reject_suspended,
+        },
+        totp::{self, Error},
+    },
+};
+use axum::{
+    Json,
+    extract::State,
+    http::header,
+    response::{AppendHeaders, IntoResponse},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use std::time::Duration;
+
+const CHALLENGE_MAX_AGE: Duration = Duration::from_mins(5);
+pub const CHALLENGE_MAX_ATTEMPTS: u32 = 5;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(totp))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = signin::totp::Request)]
+pub struct Request {
+    challenge: String,
+    /// One-time password or recovery code
+    code: String,
+}
+
+pub async fn create_challenge(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    account_id: u64,
+    remember_me: bool,
+) -> sqlx::Result<String> {
+    let challenge = generate_token();
+
+    let _ = sqlx::query(
+        "INSERT INTO signin_challenges (token, account_id, remember_me, expires_at)
+            VALUES (?, ?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(token_hasher.hash(&challenge))
+    .bind(account_id)
+    .bind(remember_me)
+    .bind(CHALLENGE_MAX_AGE.as_secs())
+    .execute(pool)
+    .await?;
+
+    Ok(challenge)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/auth/signin/totp",
+    description = "Complete a signin that requires two-factor authentication",
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Successful signin"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Invalid code or challenge",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Account suspended",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR),
+    ),
+)]
+pub async fn totp(
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
+    client: Client,
+    Rejectable(Json(Request { challenge, code }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    let (challenge_id, account_id, remember_me): (u64, u64, bool) = sqlx::query_as(
+        "SELECT id, account_id, remember_me
+            FROM signin_challenges
+            WHERE token = ? AND expires_at > NOW() AND attempts < ?
+            LIMIT 1
+            FOR UPDATE",
+    )
+    .bind(token_hasher.hash(&challenge))
+    .bind(CHALLENGE_MAX_ATTEMPTS)
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(Error::Database)?
+    .ok_or(Error::InvalidChallenge)?;
+
+    let _ =
+        sqlx::query("UPDATE signin_challenges SET attempts = attempts + 1 WHERE id = ? LIMIT 1")
+            .bind(challenge_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(Error::Database)?;
+
+    if let Err(e) = totp::verify(&mut transaction, &token_hasher, account_id, &code).await {
+        // Keep the counted attempt
+        transaction.commit().await.map_err(Error::Database)?;
+        return Err(e.into());
+    }
+
+    let _ = sqlx::query("DELETE FROM signin_challenges WHERE id = ? LIMIT 1")
+        .bind(challenge_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(Error::Database)?;
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    // Possibly suspended since the first factor was checked
+    reject_suspended(&pool, account_id).await?;
+
+    let (token, max_age) = create_session(&pool, &token_hasher, account_id, remember_me, &client)
+        .await
+        .map_err(Error::Database)?;
+
+    Ok(AppendHeaders([(
+        header::SET_COOKIE,
+        session_cookie(&token, max_age),
+    )]))
+}
@@ -1,6 +1,9 @@
 use crate::{
     ApiResult, AppState,
-    users::auth::validate::{self, extract_session_token},
+    users::auth::{
+        self, remove_session_cookie,
+        validate::{self, extract_session_token},
+    },
 };
 use axum::{
     extract::State,
@@ -19,6 +22,7 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[utoipa::path(
     delete,
     path = "/users/auth/signout",
+    description = "Sign out, ending the impersonation if the session is impersonating",
     responses(
         (
             status = StatusCode::OK,
@@ -32,19 +36,19 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     ),
 )]
 pub async fn signout(
-    State(AppState { pool }): State<AppState>,
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
     headers: HeaderMap,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = sqlx::query("DELETE FROM sessions WHERE token = ? LIMIT 1")
-        .bind(&extract_session_token(&headers)?)
-        .execute(&pool)
+    let token = token_hasher.hash(&extract_session_token(&headers)?);
+
+    let _ = auth::end_sessions(&pool, auth::Sessions::Token(&token))
         .await
         .map_err(validate::Error::Database)?;
 
-    #[cfg(debug_assertions)]
-    let remove_cookie = "session_token=; Max-Age=0; Path=/; HttpOnly";
-    #[cfg(not(debug_assertions))]
-    let remove_cookie = "session_token=; Max-Age=0; Path=/; HttpOnly; Secure; SameSite=None";
-
-    Ok(AppendHeaders([(header::SET_COOKIE, remove_cookie)]))
+    Ok(AppendHeaders([(
+        header::SET_COOKIE,
+        remove_session_cookie(),
+    )]))
 }
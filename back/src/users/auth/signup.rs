@@ -1,11 +1,15 @@
 use crate::{
     ApiError, ApiResult, AppState, ErrorResponse,
-    users::{Role, email::EmailAddress},
-};
-use argon2::{
-    Argon2,
-    password_hash::{self, PasswordHasher, SaltString, rand_core::OsRng},
+    donations::CoOp,
+    mail,
+    users::{
+        Assignment, Role, Roles,
+        auth::verify_email::{self, send_verification},
+        email::EmailAddress,
+        password,
+    },
 };
+use argon2::password_hash;
 use axum::{
     Json,
     extract::State,
@@ -14,6 +18,8 @@ use axum::{
 };
 use axum_extra::extract::WithRejection as Rejectable;
 use serde::Deserialize;
+use sqlx::{Acquire, MySql, MySqlConnection};
+use time::OffsetDateTime;
 
 #[derive(utoipa::OpenApi)]
 #[openapi(paths(signup))]
@@ -37,12 +43,18 @@ pub struct Request {
 pub enum Error {
     #[error("Invalid invite")]
     ExpiredInvite,
+    #[error("Invite has already been used up")]
+    ExhaustedInvite,
+    #[error("Invite was issued for a different email")]
+    InviteEmailMismatch,
     #[error("Invite not found")]
     InviteNotFound,
     #[error("Account already exists")]
     Conflict,
     #[error("Could not hash password")]
     PasswordHash(#[from] password_hash::Error),
+    #[error("Could not send verification email")]
+    Verification(#[from] verify_email::Error),
     #[error("Could not query database")]
     Database(#[from] sqlx::Error),
 }
@@ -52,8 +64,11 @@ impl IntoResponse for Error {
         let status = match self {
             Self::InviteNotFound => StatusCode::NOT_FOUND,
             Self::ExpiredInvite => StatusCode::GONE,
+            Self::ExhaustedInvite => StatusCode::GONE,
+            Self::InviteEmailMismatch => StatusCode::FORBIDDEN,
             Self::Conflict => StatusCode::CONFLICT,
             Self::PasswordHash(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Verification(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
@@ -70,23 +85,42 @@ impl IntoResponse for Error {
     responses(
         (
             status = StatusCode::CREATED,
-            description = "Successful signup",
+            description = "Successful signup, a verification link is emailed unless the invite was sent to the email",
         ),
         (
             status = StatusCode::UNPROCESSABLE_ENTITY,
-            description = "Invalid email",
+            description = "Invalid email, or password violating the password policy with the failed `rules` listed",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Invite was issued for a different email",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Invite not found",
         ),
         (
             status = StatusCode::CONFLICT,
             description = "Account already exists",
         ),
+        (
+            status = StatusCode::GONE,
+            description = "Invite expired or used up",
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
         ),
     ),
 )]
 pub async fn signup(
-    State(AppState { pool }): State<AppState>,
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        password_hasher,
+        breached_passwords,
+        ..
+    }): State<AppState>,
     Rejectable(
         Json(Request {
             email,
@@ -96,47 +130,118 @@ pub async fn signup(
         _,
     ): Rejectable<Json<Request>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
+    password::check(&pool, &breached_passwords, &email, &password).await?;
+
     let mut transaction = pool.begin().await.map_err(Error::Database)?;
 
-    let (id, role): (u64, Role) =
-        sqlx::query_as("SELECT id, role FROM invites WHERE code = ? LIMIT 1")
-            .bind(&invite)
-            .fetch_optional(&pool)
-            .await
-            .map_err(Error::Database)?
-            .ok_or(Error::InviteNotFound)?;
+    let (invite_id, assignment, email_verified) =
+        redeem_invite(&mut transaction, &invite, &email).await?;
+    let id = create_account(
+        &mut *transaction,
+        &password_hasher,
+        &email,
+        &password,
+        assignment,
+        Some(invite_id),
+        email_verified,
+    )
+    .await?;
 
-    if sqlx::query(
-        "UPDATE invites SET expires_at = NOW() WHERE id = ? AND expires_at > NOW() LIMIT 1",
+    if !email_verified {
+        send_verification(
+            &mut transaction,
+            &token_hasher,
+            id,
+            &email,
+            &mail::VERIFY_EMAIL,
+            &frontend_url,
+        )
+        .await?;
+    }
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    Ok(StatusCode::CREATED)
+}
+
+/// Returns the invite's id, the role in a co-op it grants and whether it was sent to `email`
+pub async fn redeem_invite(
+    connection: &mut MySqlConnection,
+    code: &str,
+    email: &EmailAddress,
+) -> Result<(u64, Assignment, bool), Error> {
+    let (id, role, co_op, bound_email, max_uses, uses, expires_at): (
+        u64,
+        Role,
+        Option<CoOp>,
+        Option<String>,
+        u32,
+        u32,
+        OffsetDateTime,
+    ) = sqlx::query_as(
+        "SELECT id, role, co_op, email, max_uses, uses, expires_at
+            FROM invites
+            WHERE code = ?
+            LIMIT 1
+            FOR UPDATE",
     )
-    .bind(id)
-    .execute(&mut *transaction)
-    .await
-    .map_err(Error::Database)?
-    .rows_affected()
-    .eq(&0)
-    {
+    .bind(code)
+    .fetch_optional(&mut *connection)
+    .await?
+    .ok_or(Error::InviteNotFound)?;
+
+    if expires_at <= OffsetDateTime::now_utc() {
         Err(Error::ExpiredInvite)?
     }
+    if uses >= max_uses {
+        Err(Error::ExhaustedInvite)?
+    }
+    let email_bound = match bound_email {
+        Some(bound) if !bound.eq_ignore_ascii_case(&email.to_string()) => {
+            Err(Error::InviteEmailMismatch)?
+        }
+        bound => bound.is_some(),
+    };
 
-    match sqlx::query("INSERT INTO accounts (email, password, role) VALUES (?, ?, ?)")
-        .bind(&email)
-        .bind(
-            Argon2::default()
-                .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
-                .map_err(Error::PasswordHash)?
-                .to_string(),
-        )
-        .bind(role)
-        .execute(&mut *transaction)
-        .await
-    {
-        Ok(_) => {
-            transaction.commit().await.map_err(Error::Database)?;
+    let _ = sqlx::query("UPDATE invites SET uses = uses + 1 WHERE id = ? LIMIT 1")
+        .bind(id)
+        .execute(&mut *connection)
+        .await?;
 
-            Ok(StatusCode::CREATED)
-        }
+    Ok((id, Assignment { role, co_op }, email_bound))
+}
+
+pub async fn create_account(
+    connection: impl Acquire<'_, Database = MySql>,
+    password_hasher: &password::Hasher,
+    email: &EmailAddress,
+    password: &str,
+    assignment: Assignment,
+    invite_id: Option<u64>,
+    email_verified: bool,
+) -> Result<u64, Error> {
+    let mut transaction = connection.begin().await?;
+
+    let id = match sqlx::query(
+        "INSERT INTO accounts (email, password, invite_id, email_verified_at)
+            VALUES (?, ?, ?, IF(?, NOW(), NULL))",
+    )
+    .bind(email)
+    .bind(password_hasher.hash(password)?)
+    .bind(invite_id)
+    .bind(email_verified)
+    .execute(&mut *transaction)
+    .await
+    {
+        Ok(res) => res.last_insert_id(),
         Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(Error::Conflict)?,
         Err(e) => Err(Error::Database(e))?,
-    }
+    };
+    Roles::new(vec![assignment])
+        .store(&mut *transaction, id)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(id)
 }
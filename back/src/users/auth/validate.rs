@@ -1,11 +1,27 @@
-use crate::{ApiError, ApiResult, AppState, ErrorResponse, users::Role};
+use crate::{
+    ApiError, ApiResult, AppState, ErrorResponse,
+    donations::CoOp,
+    permissions::Permission,
+    users::{
+        Roles,
+        auth::{
+            self, Renewal, TokenHasher,
+            api_token::{self, Scope},
+        },
+    },
+};
 use axum::{
     Json,
-    extract::{FromRequestParts, State},
+    extract::FromRequestParts,
     http::{HeaderMap, StatusCode, header, request::Parts},
     response::{IntoResponse, Response},
 };
 use sqlx::MySqlPool;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// How long after signing in or reauthenticating sensitive changes are allowed
+pub const REAUTHENTICATION_MAX_AGE: Duration = Duration::from_mins(5);
 
 #[derive(utoipa::OpenApi)]
 #[openapi(paths(validate))]
@@ -27,6 +43,18 @@ pub enum Error {
     InvalidToken,
     #[error("Insufficient permissions")]
     InsufficientPermissions,
+    #[error("Two-factor authentication must be enabled for this role")]
+    TotpRequired,
+    #[error("Reauthenticate with the current password to make this change")]
+    ReauthenticationRequired,
+    #[error("API token lacks the scope for this route")]
+    InsufficientScope,
+    #[error("Account is limited to another co-op")]
+    OtherCoOp,
+    #[error("Account is suspended")]
+    Suspended,
+    #[error("Not allowed while impersonating")]
+    Impersonating,
     #[error("Could not query database")]
     Database(#[from] sqlx::Error),
 }
@@ -36,6 +64,12 @@ impl IntoResponse for Error {
         let status = match self {
             Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::InsufficientPermissions => StatusCode::FORBIDDEN,
+            Self::TotpRequired => StatusCode::FORBIDDEN,
+            Self::ReauthenticationRequired => StatusCode::FORBIDDEN,
+            Self::InsufficientScope => StatusCode::FORBIDDEN,
+            Self::OtherCoOp => StatusCode::FORBIDDEN,
+            Self::Suspended => StatusCode::FORBIDDEN,
+            Self::Impersonating => StatusCode::FORBIDDEN,
             _ => StatusCode::UNAUTHORIZED,
         };
 
@@ -58,52 +92,322 @@ impl IntoResponse for Error {
             status = StatusCode::UNAUTHORIZED,
             description = "Unsuccessful login",
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Account suspended",
+        ),
         (status = StatusCode::INTERNAL_SERVER_ERROR),
     ),
 )]
-pub async fn validate(
-    State(AppState { pool }): State<AppState>,
-    headers: HeaderMap,
-) -> ApiResult<impl IntoResponse> {
-    sqlx::query(
-        "SELECT 1
-            FROM sessions
-            WHERE token = ?
+pub async fn validate(_: UncheckedSession) -> impl IntoResponse {
+    StatusCode::OK
+}
+
+pub struct Session {
+    pub id: u64,
+    pub account_id: u64,
+    pub roles: Roles,
+    /// Signed in or reauthenticated within `REAUTHENTICATION_MAX_AGE`
+    pub reauthenticated: bool,
+    /// Superadmin acting as the account, see `POST /users/{id}/impersonate`
+    pub impersonator_id: Option<u64>,
+}
+
+impl Session {
+    /// Guards what only the account's owner may do against superadmins impersonating it
+    pub fn forbid_impersonation(&self) -> Result<(), Error> {
+        match self.impersonator_id {
+            Some(_) => Err(Error::Impersonating),
+            None => Ok(()),
+        }
+    }
+
+    /// Guards sensitive changes against someone who merely got hold of the session,
+    /// they are never allowed while impersonating
+    pub fn require_reauthentication(&self) -> Result<(), Error> {
+        self.forbid_impersonation()?;
+        if self.reauthenticated {
+            Ok(())
+        } else {
+            Err(Error::ReauthenticationRequired)
+        }
+    }
+}
+
+/// Session that is not rejected for a missing second factor, so one can be enrolled
+pub struct UncheckedSession(pub Session);
+
+pub async fn get_session(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    parts: &Parts,
+) -> ApiResult<Session> {
+    let (session, totp_missing) = fetch_session(pool, token_hasher, parts).await?;
+
+    if totp_missing
+        && session
+            .roles
+            .grants(pool, Permission::TotpRequired)
+            .await
+            .map_err(Error::Database)?
+    {
+        Err(Error::TotpRequired)?
+    }
+
+    Ok(session)
+}
+
+pub async fn get_unchecked_session(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    parts: &Parts,
+) -> ApiResult<UncheckedSession> {
+    Ok(UncheckedSession(
+        fetch_session(pool, token_hasher, parts).await?.0,
+    ))
+}
+
+#[derive(sqlx::FromRow)]
+struct SessionRow {
+    id: u64,
+    account_id: u64,
+    suspended: bool,
+    reauthenticated: bool,
+    impersonator_id: Option<u64>,
+    impersonator_active: Option<bool>,
+    totp_missing: bool,
+    remember_me: bool,
+    created_at: OffsetDateTime,
+    expires_at: OffsetDateTime,
+}
+
+/// Returns the session and whether it lacks a second factor that the settings require
+/// of roles with `totp.required`, sliding its expiry once authenticated
+async fn fetch_session(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    Parts {
+        headers,
+        extensions,
+        ..
+    }: &Parts,
+) -> ApiResult<(Session, bool)> {
+    let token = token_hasher.hash(&extract_session_token(headers)?);
+
+    let SessionRow {
+        id,
+        account_id,
+        suspended,
+        reauthenticated,
+        impersonator_id,
+        impersonator_active,
+        totp_missing,
+        remember_me,
+        created_at,
+        expires_at,
+    } = sqlx::query_as(
+        "SELECT sessions.id, accounts.id AS account_id,
+                accounts.suspended_at IS NOT NULL AS suspended,
+                sessions.reauthenticated_at > NOW() - INTERVAL ? SECOND AS reauthenticated,
+                sessions.impersonator_id,
+                impersonators.suspended_at IS NULL AND impersonators.deleted_at IS NULL
+                    AS impersonator_active,
+                settings.require_totp AND accounts.totp_enabled_at IS NULL
+                    AND NOT EXISTS(SELECT 1 FROM passkeys WHERE passkeys.account_id = accounts.id)
+                    AS totp_missing,
+                sessions.remember_me, sessions.created_at, sessions.expires_at
+            FROM sessions JOIN accounts ON sessions.account_id = accounts.id
+                LEFT JOIN accounts AS impersonators ON sessions.impersonator_id = impersonators.id
+                CROSS JOIN settings
+            WHERE sessions.token = ? AND sessions.expires_at > NOW()
             LIMIT 1",
     )
-    .bind(extract_session_token(&headers)?)
-    .fetch_optional(&pool)
+    .bind(REAUTHENTICATION_MAX_AGE.as_secs())
+    .bind(token)
+    .fetch_optional(pool)
     .await
     .map_err(Error::Database)?
-    .is_some()
-    .then_some(Ok(StatusCode::OK))
-    .ok_or(Error::InvalidToken)?
-}
+    .ok_or(Error::InvalidToken)?;
 
-pub async fn get_role(pool: &MySqlPool, headers: &HeaderMap) -> ApiResult<Role> {
-    let token = extract_session_token(headers)?;
+    if suspended {
+        Err(Error::Suspended)?
+    }
+    // Impersonations end once the impersonator could no longer start them
+    if let Some(impersonator_id) = impersonator_id {
+        let impersonating = impersonator_active == Some(true)
+            && Roles::load(pool, impersonator_id)
+                .await
+                .map_err(Error::Database)?
+                .grants(pool, Permission::UsersImpersonate)
+                .await
+                .map_err(Error::Database)?;
+        if !impersonating {
+            Err(Error::InvalidToken)?
+        }
+    }
 
-    Ok(sqlx::query_scalar::<_, Role>(
-        "SELECT accounts.role
-                    FROM sessions JOIN accounts ON sessions.account_id = accounts.id
-                        WHERE sessions.token = ?
-                    LIMIT 1",
+    let max_age = auth::renew(
+        pool,
+        id,
+        remember_me,
+        created_at,
+        // Impersonation sessions end at a fixed time
+        impersonator_id.map(|_| expires_at),
     )
-    .bind(token)
-    .fetch_optional(pool)
     .await
     .map_err(Error::Database)?
-    .ok_or(Error::InvalidToken)?)
+    .ok_or(Error::InvalidToken)?;
+    if let Some(Renewal(renewed)) = extensions.get::<Renewal>() {
+        let _ = renewed.set(max_age);
+    }
+
+    let roles = Roles::load(pool, account_id)
+        .await
+        .map_err(Error::Database)?;
+
+    Ok((
+        Session {
+            id,
+            account_id,
+            roles,
+            reauthenticated,
+            impersonator_id,
+        },
+        totp_missing,
+    ))
+}
+
+/// Roles of an account accessing donations and supporters, and the co-ops in which
+/// they have the permission `Require` checked
+pub struct Member {
+    pub roles: Roles,
+    /// Filled in by `Require`, empty before
+    pub co_ops: Vec<CoOp>,
+}
+
+impl Member {
+    /// Rejects records of co-ops in which the permission is not granted
+    pub fn require_co_op(&self, co_op: CoOp) -> Result<(), Error> {
+        if self.co_ops.contains(&co_op) {
+            Ok(())
+        } else {
+            Err(Error::OtherCoOp)
+        }
+    }
+
+    /// The co-ops as a MySQL set, matched by `FIND_IN_SET(co_op, ?)`
+    pub fn co_op_set(&self) -> String {
+        self.co_ops
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<&str>>()
+            .join(",")
+    }
+}
+
+/// Authenticates with the session cookie or an `Authorization: Bearer` API token,
+/// which must have the scope of the request's route
+pub async fn get_member(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+    parts: &Parts,
+) -> ApiResult<Member> {
+    let Parts {
+        method,
+        uri,
+        headers,
+        ..
+    } = parts;
+    let Some(token) = api_token::extract_bearer_token(headers) else {
+        let Session { roles, .. } = get_session(pool, token_hasher, parts).await?;
+        return Ok(Member {
+            roles,
+            co_ops: Vec::new(),
+        });
+    };
+
+    let api_token::Owner {
+        roles,
+        suspended,
+        totp_missing,
+        scopes,
+    } = api_token::authenticate(pool, token_hasher, token)
+        .await
+        .map_err(Error::Database)?
+        .ok_or(Error::InvalidToken)?;
+
+    if suspended {
+        Err(Error::Suspended)?
+    }
+    // Tokens must not outlive the second factor their owner is required to enroll
+    if totp_missing
+        && roles
+            .grants(pool, Permission::TotpRequired)
+            .await
+            .map_err(Error::Database)?
+    {
+        Err(Error::TotpRequired)?
+    }
+
+    if !Scope::required(method, uri.path()).is_some_and(|scope| scopes.contains(&scope)) {
+        Err(Error::InsufficientScope)?
+    }
+
+    Ok(Member {
+        roles,
+        co_ops: Vec::new(),
+    })
+}
+
+impl FromRequestParts<AppState> for Session {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        AppState {
+            pool, token_hasher, ..
+        }: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        get_session(pool, token_hasher, parts).await
+    }
+}
+
+impl FromRequestParts<AppState> for UncheckedSession {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        AppState {
+            pool, token_hasher, ..
+        }: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        get_unchecked_session(pool, token_hasher, parts).await
+    }
+}
+
+impl FromRequestParts<AppState> for Member {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        AppState {
+            pool, token_hasher, ..
+        }: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        get_member(pool, token_hasher, parts).await
+    }
 }
 
-impl FromRequestParts<AppState> for Role {
+impl FromRequestParts<AppState> for Roles {
     type Rejection = ApiError;
 
     async fn from_request_parts(
-        Parts { headers, .. }: &mut Parts,
-        AppState { pool }: &AppState,
+        parts: &mut Parts,
+        AppState {
+            pool, token_hasher, ..
+        }: &AppState,
     ) -> Result<Self, Self::Rejection> {
-        get_role(pool, headers).await
+        Ok(get_member(pool, token_hasher, parts).await?.roles)
     }
 }
 
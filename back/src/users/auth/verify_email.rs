@@ -0,0 +1,179 @@
+use crate::{
+    ApiError, ApiResult, AppState, ErrorResponse,
+    mail::{self, Template},
+    users::{
+        auth::{self, TokenHasher, generate_token},
+        email::EmailAddress,
+    },
+};
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+use sqlx::MySqlConnection;
+use std::time::Duration;
+
+const EMAIL_VERIFICATION_TOKEN_MAX_AGE: Duration = Duration::from_hours(24);
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(verify_email))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = verify_email::Request)]
+pub struct Request {
+    token: String,
+}
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "VERIFY_EMAIL_")]
+pub enum Error {
+    #[error("Verification token expired or not found")]
+    InvalidToken,
+    #[error("Email is already verified")]
+    AlreadyVerified,
+    #[error("Account with this email already exists")]
+    Conflict,
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::AlreadyVerified => StatusCode::CONFLICT,
+            Self::Conflict => StatusCode::CONFLICT,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+/// Emails a link to `email` which verifies it as the email of the account once redeemed,
+/// replacing the email if it differs
+pub async fn send_verification(
+    connection: &mut MySqlConnection,
+    token_hasher: &TokenHasher,
+    account_id: u64,
+    email: &EmailAddress,
+    template: &Template,
+    frontend_url: &str,
+) -> Result<(), Error> {
+    let token = generate_token();
+
+    // Only the latest link stays valid
+    let _ = sqlx::query("DELETE FROM email_verifications WHERE account_id = ?")
+        .bind(account_id)
+        .execute(&mut *connection)
+        .await?;
+    let _ = sqlx::query(
+        "INSERT INTO email_verifications (token, account_id, email, expires_at)
+            VALUES (?, ?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(token_hasher.hash(&token))
+    .bind(account_id)
+    .bind(email)
+    .bind(EMAIL_VERIFICATION_TOKEN_MAX_AGE.as_secs())
+    .execute(&mut *connection)
+    .await?;
+
+    mail::queue(
+        &mut *connection,
+        email,
+        template,
+        &[(
+            "link",
+            &format!("{frontend_url}/login.html?verify-email={token}"),
+        )],
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/auth/verify-email",
+    description = "Redeem an emailed verification link, which applies a pending email change and then signs out all sessions",
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Email verified",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Verification token expired or not found",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "Account with the new email already exists",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR),
+    ),
+)]
+pub async fn verify_email(
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
+    Rejectable(Json(Request { token }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    let (account_id, email, changed): (u64, String, bool) = sqlx::query_as(
+        "SELECT email_verifications.account_id, email_verifications.email,
+                email_verifications.email != accounts.email
+            FROM email_verifications JOIN accounts ON accounts.id = email_verifications.account_id
+            WHERE email_verifications.token = ? AND email_verifications.expires_at > NOW()
+            LIMIT 1
+            FOR UPDATE",
+    )
+    .bind(token_hasher.hash(&token))
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(Error::Database)?
+    .ok_or(Error::InvalidToken)?;
+
+    match sqlx::query(
+        "UPDATE accounts SET email = ?, email_verified_at = NOW() WHERE id = ? LIMIT 1",
+    )
+    .bind(email)
+    .bind(account_id)
+    .execute(&mut *transaction)
+    .await
+    {
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(Error::Conflict)?,
+        Err(e) => Err(Error::Database(e))?,
+        Ok(_) => {}
+    }
+
+    let _ = sqlx::query("DELETE FROM email_verifications WHERE account_id = ?")
+        .bind(account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(Error::Database)?;
+
+    // Sessions were opened with the old email
+    if changed {
+        let _ = auth::end_sessions(&mut *transaction, auth::Sessions::Account(account_id))
+            .await
+            .map_err(Error::Database)?;
+    }
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    Ok(StatusCode::OK)
+}
@@ -1,6 +1,12 @@
 use crate::{
     ApiError, ApiResult, AppState,
-    users::{self, Role, auth::validate},
+    audit::{self, Action},
+    permissions::{Require, require},
+    users::{
+        self, Roles,
+        auth::{self, signin::lockout, validate::Session},
+        email::EmailAddress,
+    },
 };
 use axum::{
     extract::{Path, State},
@@ -8,6 +14,23 @@ use axum::{
     response::IntoResponse,
 };
 use axum_extra::extract::WithRejection as Rejectable;
+use rand::Rng;
+use serde_json::json;
+
+/// Tables holding credentials or personal data of an account, emptied when it is deleted
+/// along with its sessions
+const ACCOUNT_DATA_TABLES: [&str; 10] = [
+    "signin_challenges",
+    "recovery_codes",
+    "password_resets",
+    "email_verifications",
+    "api_tokens",
+    "oidc_identities",
+    "magic_links",
+    "passkeys",
+    "passkey_challenges",
+    "role_assignments",
+];
 
 #[derive(utoipa::OpenApi)]
 #[openapi(paths(user))]
@@ -20,6 +43,9 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[utoipa::path(
     delete,
     path = "/users/{id}",
+    security(("session" = ["users.delete"])),
+    description = "Anonymize an account with a lower role, erasing its credentials and email \
+        while keeping it as a suspended account that invites and the audit log refer to",
     responses(
         (
             status = StatusCode::NO_CONTENT,
@@ -34,37 +60,97 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
         ),
         (
             status = StatusCode::FORBIDDEN,
-            description = "Insufficient permissions",
+            description = "Insufficient permissions or reauthentication required",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "Account is the last active superadmin",
         ),
         (status = StatusCode::INTERNAL_SERVER_ERROR)
     )
 )]
 pub async fn user(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState {
+        pool,
+        password_hasher,
+        ..
+    }): State<AppState>,
+    Require(session, _): Require<require::UsersDelete, Session>,
     Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Admin {
-        Err(validate::Error::InsufficientPermissions)?
-    }
+    session.require_reauthentication()?;
+
+    let mut transaction = pool.begin().await.map_err(users::Error::Database)?;
 
-    let _ = sqlx::query("SELECT 1 FROM accounts WHERE id = ? LIMIT 1")
-        .bind(id)
-        .fetch_optional(&pool)
+    let email: EmailAddress = sqlx::query_scalar(
+        "SELECT email FROM accounts WHERE id = ? AND deleted_at IS NULL LIMIT 1 FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?
+    .ok_or(users::Error::NotFound)?;
+    let roles = Roles::load(&mut *transaction, id)
         .await
-        .map_err(users::Error::Database)?
-        .ok_or(users::Error::NotFound)?;
+        .map_err(users::Error::Database)?;
+    users::require_manageable(&session, &roles)?;
+    if roles.is_superadmin() {
+        users::require_other_superadmin(&mut *transaction, id).await?;
+    }
 
-    Ok(
-        sqlx::query("DELETE FROM accounts WHERE id = ? AND role < ? LIMIT 1")
+    for table in ACCOUNT_DATA_TABLES {
+        let _ = sqlx::query(&format!("DELETE FROM {table} WHERE account_id = ?"))
             .bind(id)
-            .bind(u8::from(role))
-            .execute(&pool)
+            .execute(&mut *transaction)
             .await
-            .map_err(users::Error::Database)?
-            .rows_affected()
-            .ne(&0)
-            .then_some(StatusCode::NO_CONTENT)
-            .ok_or(validate::Error::InsufficientPermissions)?,
+            .map_err(users::Error::Database)?;
+    }
+
+    let _ = auth::end_sessions(&mut *transaction, auth::Sessions::Account(id))
+        .await
+        .map_err(users::Error::Database)?;
+    // Failures are kept by email, which is about to be replaced
+    let _ = lockout::clear_failures(&mut *transaction, &email)
+        .await
+        .map_err(users::Error::Database)?;
+
+    // Nobody knows the new password, suspension keeps out anyone who might
+    let password: String = rand::rng()
+        .sample_iter(&rand::distr::Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect();
+    let _ = sqlx::query(
+        "UPDATE accounts
+            SET email = CONCAT('deleted-', id, '@invalid'), password = ?,
+                totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL,
+                email_verified_at = NULL, magic_link_enabled = FALSE,
+                suspended_at = COALESCE(suspended_at, NOW()), suspension_reason = NULL,
+                deleted_at = NOW()
+            WHERE id = ?
+            LIMIT 1",
+    )
+    .bind(
+        password_hasher
+            .hash(&password)
+            .map_err(users::Error::PasswordHash)?,
     )
+    .bind(id)
+    .execute(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?;
+
+    audit::record(
+        &mut *transaction,
+        Some(session.account_id),
+        Action::Delete,
+        Some(id),
+        json!({ "roles": roles.assignments() }),
+    )
+    .await
+    .map_err(users::Error::Database)?;
+
+    transaction.commit().await.map_err(users::Error::Database)?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
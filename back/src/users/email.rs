@@ -1,12 +1,34 @@
 use serde::Deserialize;
-use sqlx::{self, Database, Encode, encode::IsNull};
+use sqlx::{self, Database, Decode, Encode, encode::IsNull};
+use std::{fmt, str::FromStr};
 use utoipa::ToSchema;
 
-#[derive(Deserialize, ToSchema)]
+#[derive(Clone, Deserialize, ToSchema)]
 #[serde(transparent)]
 #[schema(value_type = String, format = Email)]
 pub struct EmailAddress(email_address::EmailAddress);
 
+impl FromStr for EmailAddress {
+    type Err = email_address::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl EmailAddress {
+    /// Lowercased, to treat differently cased spellings of an address as the same
+    pub fn normalized(&self) -> String {
+        self.0.as_str().to_lowercase()
+    }
+}
+
+impl fmt::Display for EmailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl<'q, DB> Encode<'q, DB> for EmailAddress
 where
     DB: Database,
@@ -20,6 +42,16 @@ where
     }
 }
 
+impl<'r, DB> Decode<'r, DB> for EmailAddress
+where
+    DB: Database,
+    String: Decode<'r, DB>,
+{
+    fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(String::decode(value)?.parse()?)
+    }
+}
+
 impl<DB> sqlx::Type<DB> for EmailAddress
 where
     DB: Database,
@@ -1,6 +1,7 @@
 use crate::{
     ApiError, ApiResult, AppState,
-    users::{self, Response, Role, auth::validate},
+    permissions::{Require, require},
+    users::{self, Response},
 };
 use axum::{
     Json,
@@ -21,6 +22,7 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[utoipa::path(
     get,
     path = "/users",
+    security(("session" = ["users.read"])),
     responses(
         (
             status = StatusCode::OK,
@@ -38,32 +40,21 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     ),
 )]
 pub async fn users(
-    State(AppState { pool }): State<AppState>,
-    role: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    _: Require<require::UsersRead>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Admin {
-        Err(validate::Error::InsufficientPermissions)?
-    }
-
     Ok(Json(
-        sqlx::query_as::<_, (u64, String, Role)>("SELECT id, email, role FROM accounts")
+        sqlx::query_as::<_, Response>(users::SELECT_RESPONSE)
             .fetch_all(&pool)
             .await
-            .map_err(users::Error::Database)?
-            .into_iter()
-            .map(|(id, email, role)| Response {
-                id,
-                email,
-                role,
-                role_rank: u8::from(role),
-            })
-            .collect::<Vec<_>>(),
+            .map_err(users::Error::Database)?,
     ))
 }
 
 #[utoipa::path(
     get,
     path = "/users/{id}",
+    security(("session" = ["users.read"])),
     responses(
         (
             status = StatusCode::OK,
@@ -85,30 +76,17 @@ pub async fn users(
     ),
 )]
 pub async fn user(
-    State(AppState { pool }): State<AppState>,
+    State(AppState { pool, .. }): State<AppState>,
     Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
-    role: Role,
+    _: Require<require::UsersRead>,
 ) -> ApiResult<impl IntoResponse> {
-    if role < Role::Admin {
-        Err(validate::Error::InsufficientPermissions)?
-    }
-
-    let user: (u64, String, Role) =
-        sqlx::query_as("SELECT id, email, role FROM accounts WHERE id = ? LIMIT 1")
+    let user: Response =
+        sqlx::query_as(&format!("{} WHERE id = ? LIMIT 1", users::SELECT_RESPONSE))
             .bind(id)
             .fetch_optional(&pool)
             .await
             .map_err(users::Error::Database)?
             .ok_or(users::Error::NotFound)?;
 
-    let (id, email, role) = user;
-
-    let user = Response {
-        id,
-        email,
-        role,
-        role_rank: u8::from(role),
-    };
-
     Ok((StatusCode::OK, Json(user)))
 }
@@ -0,0 +1,123 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    audit::{self, Action},
+    permissions::{Require, require},
+    users::{
+        self, Roles,
+        auth::{
+            self, Client, session_cookie,
+            validate::{self, Session},
+        },
+    },
+};
+use axum::{
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{AppendHeaders, IntoResponse},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde_json::json;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(impersonate))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{id}/impersonate",
+    security(("session" = ["users.impersonate"])),
+    description = "Act as an account with a lower role through a session that ends after 30 minutes, \
+        replacing the own session, which is signed out. Changes requiring reauthentication are not allowed \
+        while impersonating, signing out ends the impersonation and requires signing in again.",
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+            description = "Impersonating, the session cookie is set",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "User not found",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions, account suspended, already impersonating or reauthentication required",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    )
+)]
+pub async fn impersonate(
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
+    Require(session, _): Require<require::UsersImpersonate, Session>,
+    client: Client,
+    Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    let mut transaction = pool.begin().await.map_err(users::Error::Database)?;
+
+    let suspended: bool = sqlx::query_scalar(
+        "SELECT suspended_at IS NOT NULL
+            FROM accounts
+            WHERE id = ? AND deleted_at IS NULL
+            LIMIT 1",
+    )
+    .bind(id)
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?
+    .ok_or(users::Error::NotFound)?;
+    let roles = Roles::load(&mut *transaction, id)
+        .await
+        .map_err(users::Error::Database)?;
+    users::require_manageable(&session, &roles)?;
+    if suspended {
+        Err(validate::Error::Suspended)?
+    }
+
+    let (session_id, token, max_age) = auth::create_impersonation_session(
+        &mut *transaction,
+        &token_hasher,
+        id,
+        session.account_id,
+        &client,
+    )
+    .await
+    .map_err(users::Error::Database)?;
+    // The cookie of the own session is overwritten, it would otherwise live on unnoticed
+    let _ = auth::end_sessions(
+        &mut *transaction,
+        auth::Sessions::One {
+            id: session.id,
+            account_id: session.account_id,
+        },
+    )
+    .await
+    .map_err(users::Error::Database)?;
+
+    audit::record(
+        &mut *transaction,
+        Some(session.account_id),
+        Action::ImpersonationStart,
+        Some(id),
+        json!({ "session_id": session_id, "ip": client.ip, "user_agent": client.user_agent }),
+    )
+    .await
+    .map_err(users::Error::Database)?;
+
+    transaction.commit().await.map_err(users::Error::Database)?;
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        AppendHeaders([(header::SET_COOKIE, session_cookie(&token, max_age))]),
+    ))
+}
@@ -4,9 +4,21 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     use utoipa::OpenApi;
     let mut api = ApiDoc::openapi();
     api.merge(get::openapi());
+    api.merge(passkeys::openapi());
     api.merge(patch::openapi());
+    api.merge(reauthenticate::openapi());
+    api.merge(sessions::openapi());
+    api.merge(tokens::openapi());
+    api.merge(totp::openapi());
+    api.merge(verify_email::openapi());
     api
 }
 
 pub mod get;
+pub mod passkeys;
 pub mod patch;
+pub mod reauthenticate;
+pub mod sessions;
+pub mod tokens;
+pub mod totp;
+pub mod verify_email;
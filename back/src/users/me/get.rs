@@ -1,8 +1,9 @@
 use crate::{
     ApiResult, AppState,
-    users::{self, Response, Role, auth::validate},
+    users::{self, Response, auth::validate::UncheckedSession},
 };
-use axum::{Json, extract::State, http::HeaderMap, response::IntoResponse};
+use axum::{Json, extract::State, response::IntoResponse};
+use serde::Serialize;
 
 #[derive(utoipa::OpenApi)]
 #[openapi(paths(me))]
@@ -12,13 +13,29 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     ApiDoc::openapi()
 }
 
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = users::me::Response)]
+struct MeResponse {
+    #[serde(flatten)]
+    user: Response,
+    /// Superadmin acting as the account, changes requiring reauthentication are not allowed
+    impersonator: Option<Impersonator>,
+}
+
+#[derive(Serialize, sqlx::FromRow, utoipa::ToSchema)]
+#[schema(as = users::me::Impersonator)]
+struct Impersonator {
+    id: u64,
+    email: String,
+}
+
 #[utoipa::path(
     get,
     path = "/users/me",
     responses(
         (
             status = StatusCode::OK,
-            body = Response
+            body = MeResponse
         ),
         (
             status = StatusCode::UNAUTHORIZED,
@@ -28,25 +45,24 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     ),
 )]
 pub async fn me(
-    State(AppState { pool }): State<AppState>,
-    headers: HeaderMap,
-    _: Role,
+    State(AppState { pool, .. }): State<AppState>,
+    UncheckedSession(session): UncheckedSession,
 ) -> ApiResult<impl IntoResponse> {
-    let token = validate::extract_session_token(&headers)?;
-
-    let (id, email, role): (u64, String, Role) = sqlx::query_as(
-        "SELECT accounts.id, accounts.email, accounts.role FROM sessions JOIN accounts ON accounts.id = sessions.account_id WHERE sessions.token = ? LIMIT 1",
-    )
-    .bind(token)
-    .fetch_one(&pool)
-    .await
-    .map_err(users::Error::Database)?;
+    let user: Response =
+        sqlx::query_as(&format!("{} WHERE id = ? LIMIT 1", users::SELECT_RESPONSE))
+            .bind(session.account_id)
+            .fetch_one(&pool)
+            .await
+            .map_err(users::Error::Database)?;
 
-    Ok(Json(Response {
-        id,
-        email,
-        role,
+    let impersonator: Option<Impersonator> = match session.impersonator_id {
+        Some(id) => sqlx::query_as("SELECT id, email FROM accounts WHERE id = ? LIMIT 1")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await
+            .map_err(users::Error::Database)?,
+        None => None,
+    };
 
-        role_rank: u8::from(role),
-    }))
+    Ok(Json(MeResponse { user, impersonator }))
 }
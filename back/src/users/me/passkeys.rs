@@ -0,0 +1,41 @@
+use serde::Serialize;
+
+#[derive(utoipa::OpenApi)]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(get::openapi());
+    api.merge(post::openapi());
+    api.merge(confirm::openapi());
+    api.merge(delete::openapi());
+    api
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = users::me::passkeys::Response)]
+struct Response {
+    id: u64,
+    name: String,
+    created_at: String,
+    last_used_at: Option<String>,
+}
+
+/// Options for `navigator.credentials.create()`, binary values are base64url encoded
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = users::me::passkeys::CreationOptions)]
+struct CreationOptions {
+    challenge: String,
+    rp_id: String,
+    user_id: String,
+    user_name: String,
+    /// COSE algorithm identifiers
+    algorithms: Vec<i64>,
+    /// Already registered credential ids
+    exclude_credentials: Vec<String>,
+}
+
+pub mod confirm;
+pub mod delete;
+pub mod get;
+pub mod post;
@@ -0,0 +1,118 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        auth::validate::UncheckedSession,
+        me::passkeys::Response,
+        passkey::{self, Error, Registration},
+    },
+};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(confirm))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+/// Response of `navigator.credentials.create()`, binary values base64url encoded
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = users::me::passkeys::confirm::Request)]
+pub struct Request {
+    name: String,
+    client_data_json: String,
+    attestation_object: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/passkeys/confirm",
+    description = "Complete registering a passkey, which can then replace the password or be used as second factor",
+    responses(
+        (
+            status = StatusCode::CREATED,
+            body = Response,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in, or challenge or response invalid",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "Passkey is already registered",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Algorithm of the passkey not supported",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn confirm(
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        ..
+    }): State<AppState>,
+    UncheckedSession(session): UncheckedSession,
+    Rejectable(
+        Json(Request {
+            name,
+            client_data_json,
+            attestation_object,
+        }),
+        _,
+    ): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    let Registration {
+        credential_id,
+        public_key,
+        sign_count,
+    } = passkey::verify_registration(
+        &mut transaction,
+        &token_hasher,
+        &passkey::rp_id(&frontend_url),
+        session.account_id,
+        &passkey::decode(&client_data_json)?,
+        &passkey::decode(&attestation_object)?,
+    )
+    .await?;
+
+    let id = match sqlx::query(
+        "INSERT INTO passkeys (account_id, credential_id, public_key, sign_count, name)
+            VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(session.account_id)
+    .bind(credential_id)
+    .bind(public_key)
+    .bind(sign_count)
+    .bind(&name)
+    .execute(&mut *transaction)
+    .await
+    {
+        Ok(res) => res.last_insert_id(),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(Error::Conflict)?,
+        Err(e) => Err(Error::Database(e))?,
+    };
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(Response {
+            id,
+            name,
+            created_at: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .map_err(Error::TimeFormat)?,
+            last_used_at: None,
+        }),
+    ))
+}
@@ -0,0 +1,61 @@
+use crate::{
+    ApiResult, AppState,
+    users::{auth::validate::UncheckedSession, me::passkeys::Response, passkey::Error},
+};
+use axum::{Json, extract::State, response::IntoResponse};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(passkeys))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/passkeys",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Response>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn passkeys(
+    State(AppState { pool, .. }): State<AppState>,
+    UncheckedSession(session): UncheckedSession,
+) -> ApiResult<impl IntoResponse> {
+    let passkeys: Vec<(u64, String, OffsetDateTime, Option<OffsetDateTime>)> = sqlx::query_as(
+        "SELECT id, name, created_at, last_used_at
+            FROM passkeys
+            WHERE account_id = ?
+            ORDER BY created_at",
+    )
+    .bind(session.account_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(Error::Database)?;
+
+    Ok(Json(
+        passkeys
+            .into_iter()
+            .map(|(id, name, created_at, last_used_at)| {
+                Ok(Response {
+                    id,
+                    name,
+                    created_at: created_at.to_utc().format(&Rfc3339)?,
+                    last_used_at: last_used_at
+                        .map(|v| v.to_utc().format(&Rfc3339))
+                        .transpose()?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?,
+    ))
+}
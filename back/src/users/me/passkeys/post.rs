@@ -0,0 +1,73 @@
+use crate::{
+    ApiResult, AppState,
+    users::{
+        auth::validate::UncheckedSession,
+        me::passkeys::CreationOptions,
+        passkey::{self, Error},
+    },
+};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(passkey))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/passkeys",
+    description = "Start registering a passkey, to be completed at `/users/me/passkeys/confirm`",
+    responses(
+        (
+            status = StatusCode::CREATED,
+            body = CreationOptions,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Reauthentication required",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn passkey(
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        ..
+    }): State<AppState>,
+    UncheckedSession(session): UncheckedSession,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    let user_name: String = sqlx::query_scalar("SELECT email FROM accounts WHERE id = ? LIMIT 1")
+        .bind(session.account_id)
+        .fetch_one(&pool)
+        .await
+        .map_err(Error::Database)?;
+    let exclude_credentials = passkey::credential_ids(&pool, session.account_id)
+        .await
+        .map_err(Error::Database)?;
+    let challenge = passkey::create_challenge(&pool, &token_hasher, Some(session.account_id))
+        .await
+        .map_err(Error::Database)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreationOptions {
+            challenge,
+            rp_id: passkey::rp_id(&frontend_url),
+            user_id: passkey::encode(&session.account_id.to_be_bytes()),
+            user_name,
+            algorithms: vec![passkey::ES256],
+            exclude_credentials,
+        }),
+    ))
+}
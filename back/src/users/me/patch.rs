@@ -1,12 +1,22 @@
 use crate::{
-    ApiError, ApiResult, AppState,
-    users::{self, Response, Role, auth::validate, email::EmailAddress},
+    ApiError, ApiResult, AppState, mail,
+    users::{
+        self, Response,
+        auth::{
+            self, session_cookie,
+            validate::Session,
+            verify_email::{self, send_verification},
+        },
+        email::EmailAddress,
+        password,
+    },
 };
-use argon2::{
-    Argon2,
-    password_hash::{PasswordHasher, SaltString, rand_core::OsRng},
+use axum::{
+    Json,
+    extract::State,
+    http::header,
+    response::{AppendHeaders, IntoResponse},
 };
-use axum::{Json, extract::State, http::HeaderMap, response::IntoResponse};
 use axum_extra::extract::WithRejection as Rejectable;
 use serde::Deserialize;
 
@@ -21,8 +31,11 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 #[derive(Deserialize, utoipa::ToSchema)]
 #[schema(as = users::me::Request)]
 pub struct Request {
+    /// Takes effect once confirmed via the link emailed to the new address
     email: Option<EmailAddress>,
     password: Option<String>,
+    /// Allow signing in via emailed links
+    magic_link_enabled: Option<bool>,
 }
 
 #[utoipa::path(
@@ -37,48 +50,127 @@ pub struct Request {
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Reauthentication required to change email, password or signin links",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "Account with the new email already exists",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Password violates the password policy, the failed `rules` are listed",
+        ),
         (status = StatusCode::INTERNAL_SERVER_ERROR)
     ),
 )]
 pub async fn me(
-    State(AppState { pool }): State<AppState>,
-    headers: HeaderMap,
-    _: Role,
-    Rejectable(Json(Request { email, password }), _): Rejectable<Json<Request>, ApiError>,
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        password_hasher,
+        breached_passwords,
+        ..
+    }): State<AppState>,
+    session: Session,
+    Rejectable(
+        Json(Request {
+            email,
+            password,
+            magic_link_enabled,
+        }),
+        _,
+    ): Rejectable<Json<Request>, ApiError>,
 ) -> ApiResult<impl IntoResponse> {
-    let token = validate::extract_session_token(&headers)?;
+    let id = session.account_id;
+    if email.is_some() || password.is_some() || magic_link_enabled.is_some() {
+        session.require_reauthentication()?;
+    }
+    let credentials_changed = password.is_some();
+
+    if let Some(password) = &password {
+        let current: EmailAddress =
+            sqlx::query_scalar("SELECT email FROM accounts WHERE id = ? LIMIT 1")
+                .bind(id)
+                .fetch_one(&pool)
+                .await
+                .map_err(users::Error::Database)?;
+        password::check(&pool, &breached_passwords, &current, password).await?;
+    }
 
     let mut transaction = pool.begin().await.map_err(users::Error::Database)?;
-    let id: u64 = sqlx::query_scalar(
-        "SELECT accounts.id FROM sessions JOIN accounts ON accounts.id = sessions.account_id WHERE sessions.token = ? LIMIT 1",
-    )
-    .bind(token)
-    .fetch_one(&pool)
-    .await
-    .map_err(users::Error::Database)?;
 
     if let Some(email) = email {
-        let _ = sqlx::query("UPDATE accounts SET email = ? WHERE id = ? LIMIT 1")
-            .bind(email)
-            .bind(id)
-            .execute(&mut *transaction)
+        let current: EmailAddress =
+            sqlx::query_scalar("SELECT email FROM accounts WHERE id = ? LIMIT 1")
+                .bind(id)
+                .fetch_one(&mut *transaction)
+                .await
+                .map_err(users::Error::Database)?;
+
+        if !current.to_string().eq_ignore_ascii_case(&email.to_string()) {
+            let taken: bool =
+                sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM accounts WHERE email = ?)")
+                    .bind(&email)
+                    .fetch_one(&mut *transaction)
+                    .await
+                    .map_err(users::Error::Database)?;
+            if taken {
+                Err(verify_email::Error::Conflict)?
+            }
+
+            send_verification(
+                &mut transaction,
+                &token_hasher,
+                id,
+                &email,
+                &mail::EMAIL_CHANGE,
+                &frontend_url,
+            )
+            .await?;
+            mail::queue(
+                &mut *transaction,
+                &current,
+                &mail::EMAIL_CHANGE_NOTICE,
+                &[("email", &email.to_string())],
+            )
             .await
             .map_err(users::Error::Database)?;
+        }
     }
     if let Some(password) = password {
-        let password = Argon2::default()
-            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
-            .map_err(users::Error::PasswordHash)?
-            .to_string();
-
-        let _ = sqlx::query("UPDATE accounts SET password = ? WHERE id = ? LIMIT 1")
-            .bind(password)
+        users::set_password(&mut *transaction, &password_hasher, id, &password).await?;
+    }
+    if let Some(magic_link_enabled) = magic_link_enabled {
+        let _ = sqlx::query("UPDATE accounts SET magic_link_enabled = ? WHERE id = ? LIMIT 1")
+            .bind(magic_link_enabled)
             .bind(id)
             .execute(&mut *transaction)
             .await
             .map_err(users::Error::Database)?;
     }
 
+    // Other sessions may have been opened with the old password
+    let cookie = if credentials_changed {
+        let _ = auth::end_sessions(
+            &mut *transaction,
+            auth::Sessions::AccountExcept {
+                account_id: id,
+                id: session.id,
+            },
+        )
+        .await
+        .map_err(users::Error::Database)?;
+        let (token, max_age) = auth::rotate_session(&mut transaction, &token_hasher, session.id)
+            .await
+            .map_err(users::Error::Database)?;
+        Some((header::SET_COOKIE, session_cookie(&token, max_age)))
+    } else {
+        None
+    };
+
     transaction.commit().await.map_err(users::Error::Database)?;
-    Ok(())
+    Ok(AppendHeaders(cookie))
 }
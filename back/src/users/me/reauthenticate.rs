@@ -0,0 +1,111 @@
+use crate::{
+    ApiError, ApiResult, AppState, settings,
+    users::{
+        self,
+        auth::{
+            Client,
+            signin::{Error, lockout},
+            validate::UncheckedSession,
+        },
+        email::EmailAddress,
+    },
+};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(reauthenticate))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = users::me::reauthenticate::Request)]
+pub struct Request {
+    password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/reauthenticate",
+    description = "Confirm the current password, allowing sensitive changes for the next five minutes",
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in or password incorrect",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Impersonating",
+        ),
+        (
+            status = StatusCode::TOO_MANY_REQUESTS,
+            description = "Locked after too many failed signins, see `Retry-After`",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn reauthenticate(
+    State(AppState {
+        pool,
+        password_hasher,
+        ..
+    }): State<AppState>,
+    UncheckedSession(session): UncheckedSession,
+    client: Client,
+    Rejectable(Json(Request { password }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.forbid_impersonation()?;
+
+    let settings::Settings {
+        signin_lockout_threshold,
+        ..
+    } = settings::load(&pool).await.map_err(Error::Database)?;
+
+    let (email, hashed_password): (EmailAddress, String) =
+        sqlx::query_as("SELECT email, password FROM accounts WHERE id = ? LIMIT 1")
+            .bind(session.account_id)
+            .fetch_one(&pool)
+            .await
+            .map_err(Error::Database)?;
+
+    let ip = client.ip.as_deref();
+    if let Some(duration) = lockout::locked_for(&pool, &email, ip, signin_lockout_threshold)
+        .await
+        .map_err(Error::Database)?
+    {
+        Err(Error::Locked(duration))?
+    }
+
+    if !password_hasher
+        .verify(&hashed_password, &password)
+        .map_err(Error::PasswordHash)?
+    {
+        lockout::record_failure(&pool, &email, ip)
+            .await
+            .map_err(Error::Database)?;
+        Err(Error::IncorrectPassword)?
+    }
+    users::rehash_password(
+        &pool,
+        &password_hasher,
+        session.account_id,
+        &hashed_password,
+        &password,
+    )
+    .await;
+
+    let _ = sqlx::query("UPDATE sessions SET reauthenticated_at = NOW() WHERE id = ? LIMIT 1")
+        .bind(session.id)
+        .execute(&pool)
+        .await
+        .map_err(Error::Database)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,27 @@
+use serde::Serialize;
+
+#[derive(utoipa::OpenApi)]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(get::openapi());
+    api.merge(delete::openapi());
+    api
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = users::me::sessions::Response)]
+struct Response {
+    id: u64,
+    created_at: String,
+    last_seen_at: String,
+    expires_at: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    /// Whether this is the session making the request
+    current: bool,
+}
+
+pub mod delete;
+pub mod get;
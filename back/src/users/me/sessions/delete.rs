@@ -0,0 +1,104 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        self,
+        auth::{self, remove_session_cookie, validate::Session},
+    },
+};
+use axum::{
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{AppendHeaders, IntoResponse},
+};
+use axum_extra::extract::WithRejection as Rejectable;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(session, sessions))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/sessions/{id}",
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Session not found",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Impersonating",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    )
+)]
+pub async fn session(
+    State(AppState { pool, .. }): State<AppState>,
+    session: Session,
+    Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.forbid_impersonation()?;
+
+    auth::end_sessions(
+        &pool,
+        auth::Sessions::One {
+            id,
+            account_id: session.account_id,
+        },
+    )
+    .await
+    .map_err(users::Error::Database)?
+    .ne(&0)
+    .then_some(())
+    .ok_or(users::Error::SessionNotFound)?;
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        AppendHeaders((id == session.id).then(|| (header::SET_COOKIE, remove_session_cookie()))),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/sessions",
+    description = "Sign out everywhere, including the current session",
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Impersonating",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    )
+)]
+pub async fn sessions(
+    State(AppState { pool, .. }): State<AppState>,
+    session: Session,
+) -> ApiResult<impl IntoResponse> {
+    session.forbid_impersonation()?;
+
+    let _ = auth::end_sessions(&pool, auth::Sessions::Account(session.account_id))
+        .await
+        .map_err(users::Error::Database)?;
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        AppendHeaders([(header::SET_COOKIE, remove_session_cookie())]),
+    ))
+}
@@ -0,0 +1,75 @@
+use crate::{
+    ApiResult, AppState,
+    users::{
+        self,
+        auth::{self, SessionInfo, validate::Session},
+        me::sessions::Response,
+    },
+};
+use axum::{Json, extract::State, response::IntoResponse};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(sessions))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/sessions",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Response>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn sessions(
+    State(AppState { pool, .. }): State<AppState>,
+    session: Session,
+) -> ApiResult<impl IntoResponse> {
+    Ok(Json(
+        auth::list_sessions(&pool, Some(session.account_id))
+            .await
+            .map_err(users::Error::Database)?
+            .into_iter()
+            .map(
+                |SessionInfo {
+                     id,
+                     created_at,
+                     last_seen_at,
+                     expires_at,
+                     ip,
+                     user_agent,
+                     ..
+                 }| {
+                    Ok(Response {
+                        id,
+                        created_at: created_at
+                            .to_utc()
+                            .format(&time::format_description::well_known::Rfc3339)
+                            .map_err(users::Error::TimeFormat)?,
+                        last_seen_at: last_seen_at
+                            .to_utc()
+                            .format(&time::format_description::well_known::Rfc3339)
+                            .map_err(users::Error::TimeFormat)?,
+                        expires_at: expires_at
+                            .to_utc()
+                            .format(&time::format_description::well_known::Rfc3339)
+                            .map_err(users::Error::TimeFormat)?,
+                        ip,
+                        user_agent,
+                        current: id == session.id,
+                    })
+                },
+            )
+            .collect::<ApiResult<Vec<_>>>()?,
+    ))
+}
@@ -0,0 +1,63 @@
+use crate::{ErrorResponse, users::auth::api_token::Scope};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{self, IntoResponse},
+};
+use serde::Serialize;
+
+#[derive(utoipa::OpenApi)]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(get::openapi());
+    api.merge(post::openapi());
+    api.merge(delete::openapi());
+    api
+}
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "API_TOKEN_")]
+pub enum Error {
+    #[error("API token not found")]
+    NotFound,
+    #[error("API token may expire in at most 365 days")]
+    InvalidExpiry,
+    #[error("Could not format time")]
+    TimeFormat(#[from] time::error::Format),
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> response::Response {
+        let status = match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::InvalidExpiry => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::TimeFormat(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = users::me::tokens::Response)]
+struct Response {
+    id: u64,
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: String,
+    last_used_at: Option<String>,
+    expires_at: String,
+}
+
+pub mod delete;
+pub mod get;
+pub mod post;
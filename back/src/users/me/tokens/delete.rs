@@ -0,0 +1,55 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{auth::validate::Session, me::tokens::Error},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use axum_extra::extract::WithRejection as Rejectable;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(token))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/tokens/{id}",
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "API token not found",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    )
+)]
+pub async fn token(
+    State(AppState { pool, .. }): State<AppState>,
+    session: Session,
+    Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    Ok(
+        sqlx::query("DELETE FROM api_tokens WHERE id = ? AND account_id = ? LIMIT 1")
+            .bind(id)
+            .bind(session.account_id)
+            .execute(&pool)
+            .await
+            .map_err(Error::Database)?
+            .rows_affected()
+            .ne(&0)
+            .then_some(StatusCode::NO_CONTENT)
+            .ok_or(Error::NotFound)?,
+    )
+}
@@ -0,0 +1,73 @@
+use crate::{
+    ApiResult, AppState,
+    users::{
+        auth::{api_token, validate::Session},
+        me::tokens::{Error, Response},
+    },
+};
+use axum::{Json, extract::State, response::IntoResponse};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(tokens))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    get,
+    path = "/users/me/tokens",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Response>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn tokens(
+    State(AppState { pool, .. }): State<AppState>,
+    session: Session,
+) -> ApiResult<impl IntoResponse> {
+    let tokens: Vec<(
+        u64,
+        String,
+        String,
+        OffsetDateTime,
+        Option<OffsetDateTime>,
+        OffsetDateTime,
+    )> = sqlx::query_as(
+        "SELECT id, name, scopes, created_at, last_used_at, expires_at
+            FROM api_tokens
+            WHERE account_id = ? AND expires_at > NOW()
+            ORDER BY created_at",
+    )
+    .bind(session.account_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(Error::Database)?;
+
+    Ok(Json(
+        tokens
+            .into_iter()
+            .map(|(id, name, scopes, created_at, last_used_at, expires_at)| {
+                Ok(Response {
+                    id,
+                    name,
+                    scopes: api_token::split_scopes(&scopes),
+                    created_at: created_at.to_utc().format(&Rfc3339)?,
+                    last_used_at: last_used_at
+                        .map(|v| v.to_utc().format(&Rfc3339))
+                        .transpose()?,
+                    expires_at: expires_at.to_utc().format(&Rfc3339)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?,
+    ))
+}
@@ -0,0 +1,104 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        auth::{
+            api_token::{self, Scope},
+            validate::Session,
+        },
+        me::tokens::Error,
+    },
+};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::{Deserialize, Serialize};
+use std::{num::NonZeroU32, time::Duration};
+
+const API_TOKEN_DEFAULT_MAX_AGE: Duration = Duration::from_hours(30 * 24);
+const API_TOKEN_MAX_AGE: Duration = Duration::from_hours(365 * 24);
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(token))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = users::me::tokens::Request)]
+pub struct Request {
+    name: String,
+    scopes: Vec<Scope>,
+    /// Defaults to 30 days, at most 365 days
+    #[schema(value_type = Option<u32>, minimum = 1)]
+    expires_in_days: Option<NonZeroU32>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = users::me::tokens::CreatedResponse)]
+struct CreatedResponse {
+    id: u64,
+    /// Send as `Authorization: Bearer <token>`, it is only shown once
+    token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/tokens",
+    description = "Create a personal API token for the donations and supporters routes",
+    responses(
+        (
+            status = StatusCode::CREATED,
+            body = CreatedResponse,
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid expiry",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Reauthentication required",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn token(
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
+    session: Session,
+    Rejectable(
+        Json(Request {
+            name,
+            scopes,
+            expires_in_days,
+        }),
+        _,
+    ): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    let expires_in = expires_in_days.map_or(API_TOKEN_DEFAULT_MAX_AGE, |days| {
+        Duration::from_hours(u64::from(days.get()) * 24)
+    });
+    if expires_in > API_TOKEN_MAX_AGE {
+        Err(Error::InvalidExpiry)?
+    }
+
+    let (id, token) = api_token::create(
+        &pool,
+        &token_hasher,
+        session.account_id,
+        &name,
+        &scopes,
+        expires_in,
+    )
+    .await
+    .map_err(Error::Database)?;
+
+    Ok((StatusCode::CREATED, Json(CreatedResponse { id, token })))
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(utoipa::OpenApi)]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    let mut api = ApiDoc::openapi();
+    api.merge(post::openapi());
+    api.merge(confirm::openapi());
+    api.merge(recovery_codes::openapi());
+    api.merge(delete::openapi());
+    api
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = users::me::totp::EnrollResponse)]
+struct EnrollResponse {
+    secret: String,
+    otpauth_uri: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[schema(as = users::me::totp::RecoveryCodesResponse)]
+struct RecoveryCodesResponse {
+    recovery_codes: Vec<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = users::me::totp::Request)]
+pub struct Request {
+    /// One-time password or, where accepted, a recovery code
+    code: String,
+}
+
+pub mod confirm;
+pub mod delete;
+pub mod post;
+pub mod recovery_codes;
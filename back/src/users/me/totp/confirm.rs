@@ -0,0 +1,87 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        auth::validate::UncheckedSession,
+        me::totp::{RecoveryCodesResponse, Request},
+        totp::{self, Error},
+    },
+};
+use axum::{Json, extract::State, response::IntoResponse};
+use axum_extra::extract::WithRejection as Rejectable;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(confirm))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/totp/confirm",
+    description = "Enable two-factor authentication with a one-time password of the enrolled secret",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = RecoveryCodesResponse,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in or invalid code",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Enrollment was not started",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "Two-factor authentication is already enabled",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn confirm(
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
+    UncheckedSession(session): UncheckedSession,
+    Rejectable(Json(Request { code }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    let (email, secret, enabled): (String, Option<String>, bool) = sqlx::query_as(
+        "SELECT email, totp_secret, totp_enabled_at IS NOT NULL
+            FROM accounts
+            WHERE id = ?
+            LIMIT 1
+            FOR UPDATE",
+    )
+    .bind(session.account_id)
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(Error::Database)?;
+
+    if enabled {
+        Err(Error::AlreadyEnabled)?
+    }
+
+    let secret = totp::decrypt_secret(&token_hasher, &secret.ok_or(Error::NotEnrolled)?)?;
+    let step = totp::check(&totp::totp(&secret, email)?, &code).ok_or(Error::InvalidCode)?;
+
+    let _ = sqlx::query(
+        "UPDATE accounts SET totp_enabled_at = NOW(), totp_last_step = ? WHERE id = ? LIMIT 1",
+    )
+    .bind(step)
+    .bind(session.account_id)
+    .execute(&mut *transaction)
+    .await
+    .map_err(Error::Database)?;
+
+    let recovery_codes =
+        totp::generate_recovery_codes(&mut transaction, &token_hasher, session.account_id).await?;
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
@@ -0,0 +1,75 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        auth::validate::Session,
+        me::totp::Request,
+        totp::{self, Error},
+    },
+};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::WithRejection as Rejectable;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(totp))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    delete,
+    path = "/users/me/totp",
+    description = "Disable two-factor authentication",
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in or invalid code",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Reauthentication required",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Two-factor authentication is not enabled",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn totp(
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
+    session: Session,
+    Rejectable(Json(Request { code }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    totp::verify(&mut transaction, &token_hasher, session.account_id, &code).await?;
+
+    let _ = sqlx::query(
+        "UPDATE accounts
+            SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL
+            WHERE id = ?
+            LIMIT 1",
+    )
+    .bind(session.account_id)
+    .execute(&mut *transaction)
+    .await
+    .map_err(Error::Database)?;
+    let _ = sqlx::query("DELETE FROM recovery_codes WHERE account_id = ?")
+        .bind(session.account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(Error::Database)?;
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,82 @@
+use crate::{
+    ApiResult, AppState,
+    users::{
+        auth::validate::UncheckedSession,
+        me::totp::EnrollResponse,
+        totp::{self, Error},
+    },
+};
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(totp))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/totp",
+    description = "Start enrolling two-factor authentication, replacing any unconfirmed secret",
+    responses(
+        (
+            status = StatusCode::CREATED,
+            body = EnrollResponse,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Reauthentication required",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "Two-factor authentication is already enabled",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn totp(
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
+    UncheckedSession(session): UncheckedSession,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    let (email, enabled): (String, bool) = sqlx::query_as(
+        "SELECT email, totp_enabled_at IS NOT NULL FROM accounts WHERE id = ? LIMIT 1",
+    )
+    .bind(session.account_id)
+    .fetch_one(&pool)
+    .await
+    .map_err(Error::Database)?;
+
+    if enabled {
+        Err(Error::AlreadyEnabled)?
+    }
+
+    let secret = totp::generate_secret();
+    let otpauth_uri = totp::totp(&secret, email)?.get_url();
+
+    let _ = sqlx::query(
+        "UPDATE accounts SET totp_secret = ?, totp_last_step = NULL WHERE id = ? LIMIT 1",
+    )
+    .bind(totp::encrypt_secret(&token_hasher, &secret))
+    .bind(session.account_id)
+    .execute(&pool)
+    .await
+    .map_err(Error::Database)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(EnrollResponse {
+            secret,
+            otpauth_uri,
+        }),
+    ))
+}
@@ -0,0 +1,62 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    users::{
+        auth::validate::Session,
+        me::totp::{RecoveryCodesResponse, Request},
+        totp::{self, Error},
+    },
+};
+use axum::{Json, extract::State, response::IntoResponse};
+use axum_extra::extract::WithRejection as Rejectable;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(recovery_codes))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/totp/recovery-codes",
+    description = "Replace all recovery codes with new ones",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = RecoveryCodesResponse,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in or invalid code",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Reauthentication required",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Two-factor authentication is not enabled",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn recovery_codes(
+    State(AppState {
+        pool, token_hasher, ..
+    }): State<AppState>,
+    session: Session,
+    Rejectable(Json(Request { code }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    let mut transaction = pool.begin().await.map_err(Error::Database)?;
+
+    totp::verify(&mut transaction, &token_hasher, session.account_id, &code).await?;
+    let recovery_codes =
+        totp::generate_recovery_codes(&mut transaction, &token_hasher, session.account_id).await?;
+
+    transaction.commit().await.map_err(Error::Database)?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
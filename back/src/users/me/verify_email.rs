@@ -0,0 +1,77 @@
+use crate::{
+    ApiResult, AppState, mail,
+    users::{
+        self,
+        auth::{
+            validate::Session,
+            verify_email::{self, send_verification},
+        },
+        email::EmailAddress,
+    },
+};
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(verify_email))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/me/verify-email",
+    description = "Send a new verification link to the current email, invalidating pending links",
+    responses(
+        (
+            status = StatusCode::ACCEPTED,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "Email is already verified",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    ),
+)]
+pub async fn verify_email(
+    State(AppState {
+        pool,
+        token_hasher,
+        frontend_url,
+        ..
+    }): State<AppState>,
+    session: Session,
+) -> ApiResult<impl IntoResponse> {
+    let mut transaction = pool.begin().await.map_err(users::Error::Database)?;
+
+    let (email, verified): (EmailAddress, bool) = sqlx::query_as(
+        "SELECT email, email_verified_at IS NOT NULL FROM accounts WHERE id = ? LIMIT 1",
+    )
+    .bind(session.account_id)
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?;
+
+    if verified {
+        Err(verify_email::Error::AlreadyVerified)?
+    }
+
+    send_verification(
+        &mut transaction,
+        &token_hasher,
+        session.account_id,
+        &email,
+        &mail::VERIFY_EMAIL,
+        &frontend_url,
+    )
+    .await?;
+
+    transaction.commit().await.map_err(users::Error::Database)?;
+
+    Ok(StatusCode::ACCEPTED)
+}
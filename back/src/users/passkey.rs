@@ -0,0 +1,382 @@
+use crate::{ErrorResponse, users::auth::TokenHasher};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{self, IntoResponse},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ciborium::Value;
+use p256::ecdsa::{Signature, VerifyingKey, signature::Verifier};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::{MySqlConnection, MySqlExecutor};
+use std::time::Duration;
+use url::Url;
+
+const CHALLENGE_MAX_AGE: Duration = Duration::from_mins(5);
+/// COSE identifier of ECDSA with P-256 and SHA-256, the only supported algorithm
+pub const ES256: i64 = -7;
+
+// Flags of the authenticator data
+const USER_PRESENT: u8 = 0x01;
+const USER_VERIFIED: u8 = 0x04;
+const ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "PASSKEY_")]
+pub enum Error {
+    #[error("Passkey not found")]
+    NotFound,
+    #[error("Passkey is already registered")]
+    Conflict,
+    #[error("Passkey challenge expired or not found")]
+    InvalidChallenge,
+    #[error("Passkey response could not be verified")]
+    InvalidResponse,
+    #[error("Passkey did not verify the user")]
+    UserVerificationRequired,
+    #[error("Only ES256 passkeys are supported")]
+    UnsupportedAlgorithm,
+    #[error("Could not format time")]
+    TimeFormat(#[from] time::error::Format),
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> response::Response {
+        let status = match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict => StatusCode::CONFLICT,
+            Self::InvalidChallenge => StatusCode::UNAUTHORIZED,
+            Self::InvalidResponse => StatusCode::UNAUTHORIZED,
+            Self::UserVerificationRequired => StatusCode::UNAUTHORIZED,
+            Self::UnsupportedAlgorithm => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::TimeFormat(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+/// Relying party id passkeys are scoped to, the domain of the frontend
+pub fn rp_id(frontend_url: &str) -> String {
+    Url::parse(frontend_url)
+        .ok()
+        .and_then(|url| url.host_str().map(ToOwned::to_owned))
+        .unwrap_or_default()
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Decodes base64url as produced by `PublicKeyCredential.toJSON()`
+pub fn decode(value: &str) -> Result<Vec<u8>, Error> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| Error::InvalidResponse)
+}
+
+/// Issues a challenge for a ceremony, bound to an account unless it is a passwordless signin
+pub async fn create_challenge(
+    executor: impl MySqlExecutor<'_>,
+    token_hasher: &TokenHasher,
+    account_id: Option<u64>,
+) -> sqlx::Result<String> {
+    let challenge = encode(&rand::random::<[u8; 32]>());
+
+    let _ = sqlx::query(
+        "INSERT INTO passkey_challenges (challenge, account_id, expires_at)
+            VALUES (?, ?, NOW() + INTERVAL ? SECOND)",
+    )
+    .bind(token_hasher.hash(&challenge))
+    .bind(account_id)
+    .bind(CHALLENGE_MAX_AGE.as_secs())
+    .execute(executor)
+    .await?;
+
+    Ok(challenge)
+}
+
+/// Consumes a challenge, which must be bound to the same account
+async fn take_challenge(
+    executor: impl MySqlExecutor<'_>,
+    token_hasher: &TokenHasher,
+    challenge: &str,
+    account_id: Option<u64>,
+) -> Result<(), Error> {
+    sqlx::query(
+        "DELETE FROM passkey_challenges
+            WHERE challenge = ? AND account_id <=> ? AND expires_at > NOW()
+            LIMIT 1",
+    )
+    .bind(token_hasher.hash(challenge))
+    .bind(account_id)
+    .execute(executor)
+    .await?
+    .rows_affected()
+    .ne(&0)
+    .then_some(())
+    .ok_or(Error::InvalidChallenge)
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+/// Checks the client data of a ceremony, returning its challenge
+fn verify_client_data(client_data_json: &[u8], kind: &str, rp_id: &str) -> Result<String, Error> {
+    let ClientData {
+        kind: client_kind,
+        challenge,
+        origin,
+    } = serde_json::from_slice(client_data_json).map_err(|_| Error::InvalidResponse)?;
+
+    let origin = Url::parse(&origin).map_err(|_| Error::InvalidResponse)?;
+    let host = origin.host_str().unwrap_or_default();
+    let secure = origin.scheme() == "https" || cfg!(debug_assertions);
+    // Subdomains of the relying party may use its passkeys
+    let same_site = host == rp_id || host.ends_with(&format!(".{rp_id}"));
+
+    if client_kind != kind || !secure || !same_site {
+        Err(Error::InvalidResponse)?
+    }
+
+    Ok(challenge)
+}
+
+struct AuthenticatorData<'a> {
+    flags: u8,
+    sign_count: u32,
+    /// Credential id and COSE public key, present on registration
+    attested_credential: Option<(&'a [u8], &'a [u8])>,
+}
+
+fn parse_authenticator_data<'a>(
+    data: &'a [u8],
+    rp_id: &str,
+) -> Result<AuthenticatorData<'a>, Error> {
+    let (rp_id_hash, rest) = data.split_at_checked(32).ok_or(Error::InvalidResponse)?;
+    let (&flags, rest) = rest.split_first().ok_or(Error::InvalidResponse)?;
+    let (sign_count, rest) = rest.split_at_checked(4).ok_or(Error::InvalidResponse)?;
+
+    if *rp_id_hash != Sha256::digest(rp_id.as_bytes())[..] || flags & USER_PRESENT == 0 {
+        Err(Error::InvalidResponse)?
+    }
+
+    let attested_credential = if flags & ATTESTED_CREDENTIAL_DATA != 0 {
+        // Skips the AAGUID
+        let rest = rest.get(16..).ok_or(Error::InvalidResponse)?;
+        let (length, rest) = rest.split_at_checked(2).ok_or(Error::InvalidResponse)?;
+        let length = usize::from(u16::from_be_bytes([length[0], length[1]]));
+        Some(
+            rest.split_at_checked(length)
+                .ok_or(Error::InvalidResponse)?,
+        )
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData {
+        flags,
+        sign_count: u32::from_be_bytes(sign_count.try_into().map_err(|_| Error::InvalidResponse)?),
+        attested_credential,
+    })
+}
+
+/// Converts a COSE EC2 P-256 key into an uncompressed SEC1 point
+fn parse_public_key(cose_key: &[u8]) -> Result<Vec<u8>, Error> {
+    let Value::Map(entries) =
+        ciborium::from_reader::<Value, _>(cose_key).map_err(|_| Error::InvalidResponse)?
+    else {
+        Err(Error::InvalidResponse)?
+    };
+    let get = |label: i64| {
+        entries
+            .iter()
+            .find(|(key, _)| key.as_integer() == Some(label.into()))
+            .map(|(_, value)| value)
+    };
+    let integer = |label| get(label).and_then(Value::as_integer).map(i128::from);
+
+    // Key type EC2 on curve P-256
+    if integer(1) != Some(2) || integer(3) != Some(ES256.into()) || integer(-1) != Some(1) {
+        Err(Error::UnsupportedAlgorithm)?
+    }
+    let (Some(x), Some(y)) = (
+        get(-2).and_then(Value::as_bytes),
+        get(-3).and_then(Value::as_bytes),
+    ) else {
+        Err(Error::InvalidResponse)?
+    };
+
+    let public_key = [&[0x04], x.as_slice(), y.as_slice()].concat();
+    VerifyingKey::from_sec1_bytes(&public_key).map_err(|_| Error::InvalidResponse)?;
+    Ok(public_key)
+}
+
+pub struct Registration {
+    pub credential_id: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+/// Verifies a registration ceremony of an account, consuming its challenge.
+/// Attestation statements are not verified, the passkey is trusted by the signed in account.
+pub async fn verify_registration(
+    connection: &mut MySqlConnection,
+    token_hasher: &TokenHasher,
+    rp_id: &str,
+    account_id: u64,
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+) -> Result<Registration, Error> {
+    let challenge = verify_client_data(client_data_json, "webauthn.create", rp_id)?;
+    take_challenge(&mut *connection, token_hasher, &challenge, Some(account_id)).await?;
+
+    parse_attestation(attestation_object, rp_id)
+}
+
+/// Extracts the passkey from an attestation object, whose statement is not verified
+fn parse_attestation(attestation_object: &[u8], rp_id: &str) -> Result<Registration, Error> {
+    let Value::Map(attestation) = ciborium::from_reader::<Value, _>(attestation_object)
+        .map_err(|_| Error::InvalidResponse)?
+    else {
+        Err(Error::InvalidResponse)?
+    };
+    let authenticator_data = attestation
+        .iter()
+        .find(|(key, _)| key.as_text() == Some("authData"))
+        .and_then(|(_, value)| value.as_bytes())
+        .ok_or(Error::InvalidResponse)?;
+
+    let AuthenticatorData {
+        sign_count,
+        attested_credential,
+        ..
+    } = parse_authenticator_data(authenticator_data, rp_id)?;
+    let (credential_id, cose_key) = attested_credential.ok_or(Error::InvalidResponse)?;
+
+    Ok(Registration {
+        credential_id: credential_id.to_vec(),
+        public_key: parse_public_key(cose_key)?,
+        sign_count,
+    })
+}
+
+#[derive(Clone, Copy)]
+pub struct Assertion<'a> {
+    pub credential_id: &'a [u8],
+    pub client_data_json: &'a [u8],
+    pub authenticator_data: &'a [u8],
+    pub signature: &'a [u8],
+}
+
+/// Verifies an authentication ceremony, consuming its challenge, and returns the account
+/// and whether the user was verified (e.g. by biometrics or a PIN)
+pub async fn verify_assertion(
+    connection: &mut MySqlConnection,
+    token_hasher: &TokenHasher,
+    rp_id: &str,
+    account_id: Option<u64>,
+    assertion: Assertion<'_>,
+) -> Result<(u64, bool), Error> {
+    let Assertion {
+        credential_id,
+        client_data_json,
+        ..
+    } = assertion;
+    let challenge = verify_client_data(client_data_json, "webauthn.get", rp_id)?;
+    take_challenge(&mut *connection, token_hasher, &challenge, account_id).await?;
+
+    let (id, passkey_account_id, public_key, stored_sign_count): (u64, u64, Vec<u8>, u32) =
+        sqlx::query_as(
+            "SELECT id, account_id, public_key, sign_count
+                FROM passkeys
+                WHERE credential_id = ?
+                LIMIT 1
+                FOR UPDATE",
+        )
+        .bind(credential_id)
+        .fetch_optional(&mut *connection)
+        .await?
+        .ok_or(Error::NotFound)?;
+    if account_id.is_some_and(|account_id| account_id != passkey_account_id) {
+        Err(Error::NotFound)?
+    }
+
+    let (sign_count, user_verified) =
+        verify_signature(assertion, rp_id, &public_key, stored_sign_count)?;
+
+    let _ = sqlx::query(
+        "UPDATE passkeys SET sign_count = ?, last_used_at = NOW() WHERE id = ? LIMIT 1",
+    )
+    .bind(sign_count)
+    .bind(id)
+    .execute(&mut *connection)
+    .await?;
+
+    Ok((passkey_account_id, user_verified))
+}
+
+/// Checks an assertion against the stored public key and sign count of its passkey,
+/// returning the new sign count and whether the user was verified
+fn verify_signature(
+    Assertion {
+        client_data_json,
+        authenticator_data,
+        signature,
+        ..
+    }: Assertion<'_>,
+    rp_id: &str,
+    public_key: &[u8],
+    stored_sign_count: u32,
+) -> Result<(u32, bool), Error> {
+    let AuthenticatorData {
+        flags, sign_count, ..
+    } = parse_authenticator_data(authenticator_data, rp_id)?;
+
+    let message = [authenticator_data, &Sha256::digest(client_data_json)].concat();
+    VerifyingKey::from_sec1_bytes(public_key)
+        .map_err(|_| Error::InvalidResponse)?
+        .verify(
+            &message,
+            &Signature::from_der(signature).map_err(|_| Error::InvalidResponse)?,
+        )
+        .map_err(|_| Error::InvalidResponse)?;
+
+    // A counter that does not increase hints at a cloned authenticator, unless it is not supported
+    if (sign_count != 0 || stored_sign_count != 0) && sign_count <= stored_sign_count {
+        Err(Error::InvalidResponse)?
+    }
+
+    Ok((sign_count, flags & USER_VERIFIED != 0))
+}
+
+pub async fn credential_ids(
+    executor: impl MySqlExecutor<'_>,
+    account_id: u64,
+) -> sqlx::Result<Vec<String>> {
+    Ok(
+        sqlx::query_scalar::<_, Vec<u8>>("SELECT credential_id FROM passkeys WHERE account_id = ?")
+            .bind(account_id)
+            .fetch_all(executor)
+            .await?
+            .iter()
+            .map(|id| encode(id))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests;
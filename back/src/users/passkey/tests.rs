@@ -0,0 +1,396 @@
+use super::*;
+use crate::{
+    tests,
+    users::{Assignment, Role, auth::signup::create_account},
+};
+use p256::ecdsa::{SigningKey, signature::Signer};
+use serde_json::json;
+use sqlx::MySqlPool;
+
+const RP_ID: &str = "example.org";
+const ORIGIN: &str = "https://app.example.org";
+const CHALLENGE: &str = "challenge";
+
+/// Software authenticator with a single ES256 passkey and "none" attestation
+struct Authenticator {
+    key: SigningKey,
+    credential_id: Vec<u8>,
+    sign_count: u32,
+}
+
+impl Authenticator {
+    fn new() -> Self {
+        Self {
+            key: SigningKey::from_slice(&[0x42; 32]).expect("Key is a valid scalar"),
+            credential_id: b"credential".to_vec(),
+            sign_count: 0,
+        }
+    }
+
+    fn public_key(&self) -> Vec<u8> {
+        self.key
+            .verifying_key()
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec()
+    }
+
+    fn cose_key(&self, algorithm: i64) -> Vec<u8> {
+        let point = self.key.verifying_key().to_encoded_point(false);
+        let entries = [
+            (1, Value::from(2)),
+            (3, Value::from(algorithm)),
+            (-1, Value::from(1)),
+            (-2, Value::Bytes(point.x().unwrap().to_vec())),
+            (-3, Value::Bytes(point.y().unwrap().to_vec())),
+        ];
+        let mut cose_key = Vec::new();
+        ciborium::into_writer(
+            &Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(label, value)| (Value::from(label), value))
+                    .collect(),
+            ),
+            &mut cose_key,
+        )
+        .unwrap();
+        cose_key
+    }
+
+    fn authenticator_data(&self, rp_id: &str, flags: u8, attested: Option<&[u8]>) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend(self.sign_count.to_be_bytes());
+        if let Some(cose_key) = attested {
+            data.extend([0; 16]);
+            data.extend((self.credential_id.len() as u16).to_be_bytes());
+            data.extend(&self.credential_id);
+            data.extend(cose_key);
+        }
+        data
+    }
+
+    /// Response to `navigator.credentials.create()`, as client data and attestation object
+    fn create(&self, challenge: &str, origin: &str, algorithm: i64) -> (Vec<u8>, Vec<u8>) {
+        let authenticator_data = self.authenticator_data(
+            RP_ID,
+            USER_PRESENT | USER_VERIFIED | ATTESTED_CREDENTIAL_DATA,
+            Some(&self.cose_key(algorithm)),
+        );
+        let mut attestation_object = Vec::new();
+        ciborium::into_writer(
+            &Value::Map(vec![
+                (Value::from("fmt"), Value::from("none")),
+                (Value::from("attStmt"), Value::Map(Vec::new())),
+                (Value::from("authData"), Value::Bytes(authenticator_data)),
+            ]),
+            &mut attestation_object,
+        )
+        .unwrap();
+
+        (
+            client_data("webauthn.create", challenge, origin),
+            attestation_object,
+        )
+    }
+
+    /// Response to `navigator.credentials.get()`, as client data, authenticator data and signature
+    fn get(&mut self, challenge: &str, origin: &str, flags: u8) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        self.sign_count += 1;
+        self.sign(challenge, origin, flags)
+    }
+
+    /// Like `get`, without counting the signature
+    fn sign(&self, challenge: &str, origin: &str, flags: u8) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let client_data_json = client_data("webauthn.get", challenge, origin);
+        let authenticator_data = self.authenticator_data(RP_ID, flags, None);
+        let signature: Signature = self
+            .key
+            .sign(&[&authenticator_data[..], &Sha256::digest(&client_data_json)].concat());
+
+        (
+            client_data_json,
+            authenticator_data,
+            signature.to_der().as_bytes().to_vec(),
+        )
+    }
+}
+
+fn client_data(kind: &str, challenge: &str, origin: &str) -> Vec<u8> {
+    serde_json::to_vec(&json!({ "type": kind, "challenge": challenge, "origin": origin })).unwrap()
+}
+
+fn assertion<'a>(
+    authenticator: &'a Authenticator,
+    (client_data_json, authenticator_data, signature): &'a (Vec<u8>, Vec<u8>, Vec<u8>),
+) -> Assertion<'a> {
+    Assertion {
+        credential_id: &authenticator.credential_id,
+        client_data_json,
+        authenticator_data,
+        signature,
+    }
+}
+
+#[test]
+fn registers_passkey() {
+    let authenticator = Authenticator::new();
+    let (client_data_json, attestation_object) = authenticator.create(CHALLENGE, ORIGIN, ES256);
+
+    let challenge = verify_client_data(&client_data_json, "webauthn.create", RP_ID).unwrap();
+    assert_eq!(challenge, CHALLENGE);
+
+    let Registration {
+        credential_id,
+        public_key,
+        sign_count,
+    } = parse_attestation(&attestation_object, RP_ID).unwrap();
+    assert_eq!(credential_id, authenticator.credential_id);
+    assert_eq!(public_key, authenticator.public_key());
+    assert_eq!(sign_count, 0);
+}
+
+#[test]
+fn rejects_other_algorithms() {
+    let authenticator = Authenticator::new();
+    // RS256
+    let (_, attestation_object) = authenticator.create(CHALLENGE, ORIGIN, -257);
+
+    assert!(matches!(
+        parse_attestation(&attestation_object, RP_ID),
+        Err(Error::UnsupportedAlgorithm)
+    ));
+}
+
+#[test]
+fn rejects_other_origins_and_ceremonies() {
+    let authenticator = Authenticator::new();
+
+    for origin in ["https://example.com", "https://notexample.org"] {
+        let (client_data_json, _) = authenticator.create(CHALLENGE, origin, ES256);
+        assert!(matches!(
+            verify_client_data(&client_data_json, "webauthn.create", RP_ID),
+            Err(Error::InvalidResponse)
+        ));
+    }
+
+    let (client_data_json, _) = authenticator.create(CHALLENGE, "https://example.org", ES256);
+    assert!(verify_client_data(&client_data_json, "webauthn.create", RP_ID).is_ok());
+    assert!(matches!(
+        verify_client_data(&client_data_json, "webauthn.get", RP_ID),
+        Err(Error::InvalidResponse)
+    ));
+
+    // Authenticator data is scoped to the relying party too
+    let (_, attestation_object) = authenticator.create(CHALLENGE, ORIGIN, ES256);
+    assert!(matches!(
+        parse_attestation(&attestation_object, "example.com"),
+        Err(Error::InvalidResponse)
+    ));
+}
+
+#[test]
+fn verifies_signature() {
+    let mut authenticator = Authenticator::new();
+    let public_key = authenticator.public_key();
+
+    let response = authenticator.get(CHALLENGE, ORIGIN, USER_PRESENT | USER_VERIFIED);
+    assert_eq!(
+        verify_signature(assertion(&authenticator, &response), RP_ID, &public_key, 0).unwrap(),
+        (1, true)
+    );
+
+    let response = authenticator.get(CHALLENGE, ORIGIN, USER_PRESENT);
+    assert_eq!(
+        verify_signature(assertion(&authenticator, &response), RP_ID, &public_key, 1).unwrap(),
+        (2, false)
+    );
+
+    // Signed by the authenticator, but not over this client data
+    let (_, authenticator_data, signature) = authenticator.get(CHALLENGE, ORIGIN, USER_PRESENT);
+    let tampered = (
+        client_data("webauthn.get", "other", ORIGIN),
+        authenticator_data,
+        signature,
+    );
+    assert!(matches!(
+        verify_signature(assertion(&authenticator, &tampered), RP_ID, &public_key, 2),
+        Err(Error::InvalidResponse)
+    ));
+
+    let other_key = Authenticator {
+        key: SigningKey::from_slice(&[0x43; 32]).unwrap(),
+        ..Authenticator::new()
+    }
+    .public_key();
+    let response = authenticator.get(CHALLENGE, ORIGIN, USER_PRESENT);
+    assert!(matches!(
+        verify_signature(assertion(&authenticator, &response), RP_ID, &other_key, 3),
+        Err(Error::InvalidResponse)
+    ));
+}
+
+#[test]
+fn rejects_sign_count_not_increasing() {
+    let mut authenticator = Authenticator::new();
+    let public_key = authenticator.public_key();
+    authenticator.sign_count = 4;
+
+    let response = authenticator.get(CHALLENGE, ORIGIN, USER_PRESENT);
+    for stored_sign_count in [5, 6] {
+        assert!(matches!(
+            verify_signature(
+                assertion(&authenticator, &response),
+                RP_ID,
+                &public_key,
+                stored_sign_count
+            ),
+            Err(Error::InvalidResponse)
+        ));
+    }
+    assert!(verify_signature(assertion(&authenticator, &response), RP_ID, &public_key, 4).is_ok());
+
+    // Authenticators without a counter always report zero
+    authenticator.sign_count = 0;
+    let response = authenticator.sign(CHALLENGE, ORIGIN, USER_PRESENT);
+    assert!(verify_signature(assertion(&authenticator, &response), RP_ID, &public_key, 0).is_ok());
+}
+
+async fn create_test_account(pool: &MySqlPool, email: &str) -> u64 {
+    create_account(
+        pool,
+        &tests::app_state(pool.clone()).password_hasher,
+        &email.parse().unwrap(),
+        "password",
+        Assignment {
+            role: Role::Editor,
+            co_op: None,
+        },
+        None,
+        true,
+    )
+    .await
+    .unwrap()
+}
+
+#[sqlx::test]
+#[ignore = "needs a MySQL server at DATABASE_URL"]
+async fn registers_and_signs_in_once_per_challenge(pool: MySqlPool) {
+    let token_hasher = TokenHasher::new(b"test");
+    let account_id = create_test_account(&pool, "someone@example.org").await;
+    let other_account_id = create_test_account(&pool, "other@example.org").await;
+    let mut authenticator = Authenticator::new();
+    let mut connection = pool.acquire().await.unwrap();
+
+    // Challenges are bound to the account they were issued to
+    let challenge = create_challenge(&pool, &token_hasher, Some(other_account_id))
+        .await
+        .unwrap();
+    let (client_data_json, attestation_object) = authenticator.create(&challenge, ORIGIN, ES256);
+    assert!(matches!(
+        verify_registration(
+            &mut connection,
+            &token_hasher,
+            RP_ID,
+            account_id,
+            &client_data_json,
+            &attestation_object,
+        )
+        .await,
+        Err(Error::InvalidChallenge)
+    ));
+
+    let challenge = create_challenge(&pool, &token_hasher, Some(account_id))
+        .await
+        .unwrap();
+    let (client_data_json, attestation_object) = authenticator.create(&challenge, ORIGIN, ES256);
+    let Registration {
+        credential_id,
+        public_key,
+        sign_count,
+    } = verify_registration(
+        &mut connection,
+        &token_hasher,
+        RP_ID,
+        account_id,
+        &client_data_json,
+        &attestation_object,
+    )
+    .await
+    .unwrap();
+    assert!(matches!(
+        verify_registration(
+            &mut connection,
+            &token_hasher,
+            RP_ID,
+            account_id,
+            &client_data_json,
+            &attestation_object,
+        )
+        .await,
+        Err(Error::InvalidChallenge)
+    ));
+
+    let _ = sqlx::query(
+        "INSERT INTO passkeys (account_id, credential_id, public_key, sign_count, name)
+            VALUES (?, ?, ?, ?, 'test')",
+    )
+    .bind(account_id)
+    .bind(credential_id)
+    .bind(public_key)
+    .bind(sign_count)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let challenge = create_challenge(&pool, &token_hasher, None).await.unwrap();
+    let response = authenticator.get(&challenge, ORIGIN, USER_PRESENT | USER_VERIFIED);
+    let signed_in = verify_assertion(
+        &mut connection,
+        &token_hasher,
+        RP_ID,
+        None,
+        assertion(&authenticator, &response),
+    )
+    .await
+    .unwrap();
+    assert_eq!(signed_in, (account_id, true));
+
+    assert!(matches!(
+        verify_assertion(
+            &mut connection,
+            &token_hasher,
+            RP_ID,
+            None,
+            assertion(&authenticator, &response),
+        )
+        .await,
+        Err(Error::InvalidChallenge)
+    ));
+
+    // A second factor must belong to the account that signed in with its password
+    let challenge = create_challenge(&pool, &token_hasher, Some(other_account_id))
+        .await
+        .unwrap();
+    let response = authenticator.get(&challenge, ORIGIN, USER_PRESENT);
+    assert!(matches!(
+        verify_assertion(
+            &mut connection,
+            &token_hasher,
+            RP_ID,
+            Some(other_account_id),
+            assertion(&authenticator, &response),
+        )
+        .await,
+        Err(Error::NotFound)
+    ));
+
+    let stored_sign_count: u32 =
+        sqlx::query_scalar("SELECT sign_count FROM passkeys WHERE account_id = ?")
+            .bind(account_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored_sign_count, 1);
+}
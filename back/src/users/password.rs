@@ -0,0 +1,238 @@
+use crate::{ErrorResponse, settings, users::email::EmailAddress};
+use argon2::{
+    Algorithm, Argon2, Params, Version,
+    password_hash::{
+        self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng,
+    },
+};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use sqlx::MySqlExecutor;
+use std::{env, io, path::PathBuf, sync::Arc};
+use tokio::fs;
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "PASSWORD_")]
+pub enum Error {
+    #[error("Password violates the password policy: {}", .0.iter().map(AsRef::as_ref).collect::<Vec<&str>>().join(", "))]
+    Weak(Vec<Rule>),
+    #[error("Could not read breached passwords")]
+    BreachedPasswords(#[from] io::Error),
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+/// `ErrorResponse` listing the failed rules
+#[derive(Serialize)]
+pub struct WeakResponse {
+    error: String,
+    message: String,
+    rules: Vec<Rule>,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::Weak(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::BreachedPasswords(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        match self {
+            Self::Weak(rules) => (
+                status,
+                Json(WeakResponse {
+                    error,
+                    message,
+                    rules,
+                }),
+            )
+                .into_response(),
+            _ => (status, Json(ErrorResponse { error, message })).into_response(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, strum::AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Rule {
+    /// Shorter than the minimum length
+    TooShort,
+    /// Longer than the maximum length
+    TooLong,
+    /// Equal to the email or its local part
+    MatchesEmail,
+    /// Found in the breached passwords list
+    Breached,
+}
+
+/// Rejects passwords violating the password policy of the settings
+pub async fn check(
+    executor: impl MySqlExecutor<'_>,
+    breached_passwords: &BreachedPasswords,
+    email: &EmailAddress,
+    password: &str,
+) -> Result<(), Error> {
+    let settings::Settings {
+        password_min_length,
+        password_max_length,
+        password_disallow_email,
+        ..
+    } = settings::load(executor).await?;
+
+    let mut rules = Vec::new();
+    let length = password.chars().count();
+    if length < password_min_length as usize {
+        rules.push(Rule::TooShort);
+    }
+    if length > password_max_length as usize {
+        rules.push(Rule::TooLong);
+    }
+    let email = email.to_string();
+    let local_part = email
+        .split_once('@')
+        .map_or(email.as_str(), |(local, _)| local);
+    if password_disallow_email
+        && (password.eq_ignore_ascii_case(&email) || password.eq_ignore_ascii_case(local_part))
+    {
+        rules.push(Rule::MatchesEmail);
+    }
+    if breached_passwords.contains(password).await? {
+        rules.push(Rule::Breached);
+    }
+
+    if rules.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Weak(rules))
+    }
+}
+
+/// Local copy of breached password hashes, laid out like the k-anonymity range API of
+/// Have I Been Pwned: one `<first 5 hex digits of the SHA-1>.txt` file per range,
+/// holding `<remaining 35 hex digits>:<count>` lines
+#[derive(Clone)]
+pub struct BreachedPasswords(Option<Arc<PathBuf>>);
+
+impl BreachedPasswords {
+    /// Reads the directory from `BREACHED_PASSWORDS_DIR`, without it no password counts as breached
+    pub fn from_env() -> Self {
+        Self(
+            env::var("BREACHED_PASSWORDS_DIR")
+                .ok()
+                .map(|dir| Arc::new(dir.into())),
+        )
+    }
+
+    pub async fn contains(&self, password: &str) -> io::Result<bool> {
+        let Some(dir) = &self.0 else {
+            return Ok(false);
+        };
+
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        let range = match fs::read_to_string(dir.join(format!("{prefix}.txt"))).await {
+            Ok(range) => range,
+            // Partial copies are fine, missing ranges contain no breached passwords
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => Err(e)?,
+        };
+
+        Ok(range.lines().any(|line| {
+            line.split_once(':')
+                .map_or(line, |(hash, _)| hash)
+                .trim()
+                .eq_ignore_ascii_case(suffix)
+        }))
+    }
+}
+
+/// Argon2id with the configured cost, used for every password that is hashed or verified
+#[derive(Clone)]
+pub struct Hasher {
+    argon2: Argon2<'static>,
+    /// Verified against when there is no account, so signins take as long either way
+    dummy_hash: Arc<str>,
+}
+
+impl Hasher {
+    pub fn new(params: Params) -> Self {
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let dummy_hash = argon2
+            .hash_password(b"", &SaltString::generate(&mut OsRng))
+            .expect("Unable to hash with the Argon2 parameters")
+            .to_string()
+            .into();
+        Self { argon2, dummy_hash }
+    }
+
+    /// Reads `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS` and `ARGON2_PARALLELISM`,
+    /// defaulting to the OWASP recommendation of 19 MiB, 2 iterations and 1 lane
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: u32| {
+            env::var(name).map_or(default, |v| {
+                v.parse().unwrap_or_else(|_| panic!("Invalid {name}"))
+            })
+        };
+
+        Self::new(
+            Params::new(
+                var("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST),
+                var("ARGON2_ITERATIONS", Params::DEFAULT_T_COST),
+                var("ARGON2_PARALLELISM", Params::DEFAULT_P_COST),
+                None,
+            )
+            .expect("Invalid Argon2 parameters"),
+        )
+    }
+
+    pub fn hash(&self, password: &str) -> Result<String, password_hash::Error> {
+        Ok(self
+            .argon2
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))?
+            .to_string())
+    }
+
+    /// Returns whether the password matches the PHC string, whatever parameters it was hashed with
+    pub fn verify(&self, hash: &str, password: &str) -> Result<bool, password_hash::Error> {
+        match self
+            .argon2
+            .verify_password(password.as_bytes(), &PasswordHash::new(hash)?)
+        {
+            Ok(()) => Ok(true),
+            Err(password_hash::Error::Password) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Spends as long as `verify` without a hash to verify against
+    pub fn verify_dummy(&self, password: &str) {
+        let _ = self.verify(&self.dummy_hash, password);
+    }
+
+    /// Returns whether the PHC string uses another algorithm or parameters than new hashes
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(hash) = PasswordHash::new(hash) else {
+            return true;
+        };
+        let params = self.argon2.params();
+
+        hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+            || Params::try_from(&hash).ok().is_none_or(|stored| {
+                (stored.m_cost(), stored.t_cost(), stored.p_cost())
+                    != (params.m_cost(), params.t_cost(), params.p_cost())
+            })
+    }
+}
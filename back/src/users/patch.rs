@@ -0,0 +1,141 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    audit::{self, Action},
+    permissions::{Require, require},
+    users::{
+        self, Assignment, Response, Roles,
+        auth::{self, api_token, validate::Session},
+    },
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(user))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = users::patch::Request)]
+pub struct Request {
+    /// Replaces all roles of the account, one per co-op or for all co-ops without one
+    roles: Vec<Assignment>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/users/{id}",
+    security(("session" = ["users.role"])),
+    description = "Change the roles of an account per co-op, only accounts below the own role \
+        in each of their co-ops can be given roles below it in those co-ops, except by superadmins \
+        who may change any roles including their own. \
+        Changing the roles signs the account out everywhere and deletes its API tokens",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Response,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "User not found",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions, limited to another co-op or reauthentication required",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "A co-op is assigned twice or a superadmin limited to a co-op",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "Account is the last active superadmin",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    )
+)]
+pub async fn user(
+    State(AppState { pool, .. }): State<AppState>,
+    Require(session, _): Require<require::UsersRole, Session>,
+    Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
+    Rejectable(Json(Request { roles }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+    let roles = Roles::new(roles);
+    roles.validate()?;
+
+    let mut transaction = pool.begin().await.map_err(users::Error::Database)?;
+
+    let _: u64 = sqlx::query_scalar(
+        "SELECT id FROM accounts WHERE id = ? AND deleted_at IS NULL LIMIT 1 FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?
+    .ok_or(users::Error::NotFound)?;
+    let current_roles = Roles::load(&mut *transaction, id)
+        .await
+        .map_err(users::Error::Database)?;
+
+    // Superadmins hand over their status by promoting someone else and then demoting themselves
+    if !session.roles.is_superadmin() {
+        users::require_manageable(&session, &current_roles)?;
+        users::require_manageable(&session, &roles)?;
+    }
+
+    if current_roles.is_superadmin() && !roles.is_superadmin() {
+        users::require_other_superadmin(&mut *transaction, id).await?;
+    }
+
+    if current_roles != roles {
+        roles
+            .store(&mut *transaction, id)
+            .await
+            .map_err(users::Error::Database)?;
+        audit::record(
+            &mut *transaction,
+            Some(session.account_id),
+            Action::RoleChange,
+            Some(id),
+            json!({
+                "from": current_roles.assignments(),
+                "to": roles.assignments(),
+            }),
+        )
+        .await
+        .map_err(users::Error::Database)?;
+
+        // Sessions and tokens were granted for the previous roles
+        let _ = auth::end_sessions(&mut *transaction, auth::Sessions::Account(id))
+            .await
+            .map_err(users::Error::Database)?;
+        let _ = api_token::revoke_all(&mut *transaction, id)
+            .await
+            .map_err(users::Error::Database)?;
+    }
+
+    let user: Response =
+        sqlx::query_as(&format!("{} WHERE id = ? LIMIT 1", users::SELECT_RESPONSE))
+            .bind(id)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(users::Error::Database)?;
+
+    transaction.commit().await.map_err(users::Error::Database)?;
+
+    Ok(Json(user))
+}
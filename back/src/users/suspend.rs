@@ -0,0 +1,195 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    audit::{self, Action},
+    permissions::{Require, require},
+    users::{
+        self, Roles,
+        auth::{self, validate::Session},
+    },
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(suspend, reactivate))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[schema(as = users::suspend::Request)]
+pub struct Request {
+    /// Shown to admins alongside the account
+    reason: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{id}/suspend",
+    security(("session" = ["users.suspend"])),
+    description = "Suspend an account with a lower role, signing it out and rejecting it until reactivated",
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Empty reason",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "User not found",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions, limited to another co-op or reauthentication required",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "Account is the last active superadmin",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    )
+)]
+pub async fn suspend(
+    State(AppState { pool, .. }): State<AppState>,
+    Require(session, _): Require<require::UsersSuspend, Session>,
+    Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
+    Rejectable(Json(Request { reason }), _): Rejectable<Json<Request>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    let reason = reason.trim();
+    if reason.is_empty() {
+        Err(users::Error::MissingReason)?
+    }
+
+    let mut transaction = pool.begin().await.map_err(users::Error::Database)?;
+
+    let _: u64 = sqlx::query_scalar(
+        "SELECT id FROM accounts WHERE id = ? AND deleted_at IS NULL LIMIT 1 FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?
+    .ok_or(users::Error::NotFound)?;
+    let roles = Roles::load(&mut *transaction, id)
+        .await
+        .map_err(users::Error::Database)?;
+    users::require_manageable(&session, &roles)?;
+    if roles.is_superadmin() {
+        users::require_other_superadmin(&mut *transaction, id).await?;
+    }
+
+    let _ = sqlx::query(
+        "UPDATE accounts
+            SET suspended_at = COALESCE(suspended_at, NOW()), suspension_reason = ?
+            WHERE id = ?
+            LIMIT 1",
+    )
+    .bind(reason)
+    .bind(id)
+    .execute(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?;
+    let _ = auth::end_sessions(&mut *transaction, auth::Sessions::Account(id))
+        .await
+        .map_err(users::Error::Database)?;
+
+    audit::record(
+        &mut *transaction,
+        Some(session.account_id),
+        Action::Suspend,
+        Some(id),
+        json!({ "reason": reason }),
+    )
+    .await
+    .map_err(users::Error::Database)?;
+
+    transaction.commit().await.map_err(users::Error::Database)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{id}/reactivate",
+    security(("session" = ["users.suspend"])),
+    description = "Lift the suspension of an account with a lower role",
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "User not found or deleted",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions, limited to another co-op or reauthentication required",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    )
+)]
+pub async fn reactivate(
+    State(AppState { pool, .. }): State<AppState>,
+    Require(session, _): Require<require::UsersSuspend, Session>,
+    Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    session.require_reauthentication()?;
+
+    let mut transaction = pool.begin().await.map_err(users::Error::Database)?;
+
+    let _: u64 = sqlx::query_scalar(
+        "SELECT id FROM accounts WHERE id = ? AND deleted_at IS NULL LIMIT 1 FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?
+    .ok_or(users::Error::NotFound)?;
+    let roles = Roles::load(&mut *transaction, id)
+        .await
+        .map_err(users::Error::Database)?;
+    users::require_manageable(&session, &roles)?;
+
+    let _ = sqlx::query(
+        "UPDATE accounts SET suspended_at = NULL, suspension_reason = NULL WHERE id = ? LIMIT 1",
+    )
+    .bind(id)
+    .execute(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?;
+
+    audit::record(
+        &mut *transaction,
+        Some(session.account_id),
+        Action::Reactivate,
+        Some(id),
+        json!({}),
+    )
+    .await
+    .map_err(users::Error::Database)?;
+
+    transaction.commit().await.map_err(users::Error::Database)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
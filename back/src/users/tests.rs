@@ -0,0 +1,59 @@
+use super::*;
+
+#[test]
+fn resolves_roles_per_co_op() {
+    let roles = Roles::new(vec![
+        Assignment {
+            role: Role::Admin,
+            co_op: Some(CoOp::S4l),
+        },
+        Assignment {
+            role: Role::Editor,
+            co_op: None,
+        },
+    ]);
+    assert!(roles.validate().is_ok());
+    assert_eq!(roles.in_co_op(Some(CoOp::S4l)), Some(Role::Admin));
+    assert_eq!(roles.in_co_op(Some(CoOp::StudioMatic)), Some(Role::Editor));
+    assert_eq!(roles.in_co_op(None), Some(Role::Editor));
+    assert_eq!(roles.highest(), Some(Role::Admin));
+    assert!(!roles.is_superadmin());
+
+    let limited = Roles::new(vec![Assignment {
+        role: Role::Admin,
+        co_op: Some(CoOp::StudioMatic),
+    }]);
+    assert_eq!(limited.in_co_op(Some(CoOp::S4l)), None);
+    assert_eq!(limited.in_co_op(None), None);
+    assert_eq!(Roles::default().highest(), None);
+}
+
+#[test]
+fn rejects_invalid_roles() {
+    let twice = Roles::new(vec![
+        Assignment {
+            role: Role::Admin,
+            co_op: Some(CoOp::S4l),
+        },
+        Assignment {
+            role: Role::Editor,
+            co_op: Some(CoOp::S4l),
+        },
+    ]);
+    assert!(matches!(twice.validate(), Err(Error::InvalidRoles)));
+
+    let limited_superadmin = Roles::new(vec![Assignment {
+        role: Role::SuperAdmin,
+        co_op: Some(CoOp::S4l),
+    }]);
+    assert!(matches!(
+        limited_superadmin.validate(),
+        Err(Error::InvalidRoles)
+    ));
+}
+
+#[test]
+fn names_co_ops_like_the_database() {
+    let names: Vec<&str> = CoOp::VARIANTS.iter().map(AsRef::as_ref).collect();
+    assert_eq!(names, ["S4L", "STUDIO_MATIC"]);
+}
@@ -0,0 +1,219 @@
+use crate::{ErrorResponse, users::auth::TokenHasher};
+use aes_gcm::{
+    Aes256Gcm, KeyInit,
+    aead::{Aead, AeadCore, OsRng},
+};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{self, IntoResponse},
+};
+use rand::Rng;
+use sqlx::{MySqlConnection, MySqlPool};
+use subtle::ConstantTimeEq;
+use time::OffsetDateTime;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const ISSUER: &str = "Studio-matic";
+const RECOVERY_CODE_COUNT: usize = 10;
+const SECRET_KEY_PURPOSE: &str = "totp_secret";
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Debug, thiserror::Error, strum::AsRefStr, strum::VariantNames)]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+#[strum(prefix = "TOTP_")]
+pub enum Error {
+    #[error("Two-factor authentication is already enabled")]
+    AlreadyEnabled,
+    #[error("Two-factor authentication is not set up")]
+    NotEnrolled,
+    #[error("Invalid two-factor authentication code")]
+    InvalidCode,
+    #[error("Sign in challenge expired or not found")]
+    InvalidChallenge,
+    #[error("Could not decode secret")]
+    Secret(#[from] totp_rs::SecretParseError),
+    #[error("Could not decrypt secret")]
+    Decryption,
+    #[error("Could not create one-time password")]
+    Totp(#[from] totp_rs::TotpUrlError),
+    #[error("Could not query database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> response::Response {
+        let status = match self {
+            Self::AlreadyEnabled => StatusCode::CONFLICT,
+            Self::NotEnrolled => StatusCode::NOT_FOUND,
+            Self::InvalidCode => StatusCode::UNAUTHORIZED,
+            Self::InvalidChallenge => StatusCode::UNAUTHORIZED,
+            Self::Secret(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Decryption => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Totp(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        let error = self.as_ref().to_string();
+        let message = self.to_string();
+
+        (status, Json(ErrorResponse { error, message })).into_response()
+    }
+}
+
+pub fn generate_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+/// Encrypts a secret for storage, so a copy of the database alone does not reveal second factors
+pub fn encrypt_secret(token_hasher: &TokenHasher, secret: &str) -> String {
+    let cipher = Aes256Gcm::new(&token_hasher.derive_key(SECRET_KEY_PURPOSE).into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, secret.as_bytes())
+        .expect("Secrets are far shorter than the AES-GCM limit");
+    hex::encode([&nonce[..], &ciphertext].concat())
+}
+
+pub fn decrypt_secret(token_hasher: &TokenHasher, encrypted: &str) -> Result<String, Error> {
+    let cipher = Aes256Gcm::new(&token_hasher.derive_key(SECRET_KEY_PURPOSE).into());
+    let encrypted = hex::decode(encrypted).map_err(|_| Error::Decryption)?;
+    if encrypted.len() < NONCE_LENGTH {
+        Err(Error::Decryption)?
+    }
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+    let secret = cipher
+        .decrypt(nonce.into(), ciphertext)
+        .map_err(|_| Error::Decryption)?;
+    String::from_utf8(secret).map_err(|_| Error::Decryption)
+}
+
+/// Encrypts secrets stored before they were encrypted, run at startup since
+/// migrations do not know `TOKEN_SECRET`
+pub async fn encrypt_plaintext_secrets(
+    pool: &MySqlPool,
+    token_hasher: &TokenHasher,
+) -> sqlx::Result<()> {
+    let plaintext: Vec<(u64, String)> = sqlx::query_as(
+        "SELECT id, totp_secret_plaintext FROM accounts WHERE totp_secret_plaintext IS NOT NULL",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for (account_id, secret) in plaintext {
+        let _ = sqlx::query(
+            "UPDATE accounts
+                SET totp_secret = ?, totp_secret_plaintext = NULL
+                WHERE id = ? AND totp_secret_plaintext = ?
+                LIMIT 1",
+        )
+        .bind(encrypt_secret(token_hasher, &secret))
+        .bind(account_id)
+        .bind(&secret)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub fn totp(secret: &str, email: String) -> Result<TOTP, Error> {
+    Ok(TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        Secret::Encoded(secret.to_owned()).to_bytes()?,
+        Some(ISSUER.to_owned()),
+        email,
+    )?)
+}
+
+/// Checks a code against a secret, returning the time step it is valid for
+pub fn check(totp: &TOTP, code: &str) -> Option<u64> {
+    let step = OffsetDateTime::now_utc().unix_timestamp().unsigned_abs() / totp.step;
+    [step - 1, step, step + 1].into_iter().find(|step| {
+        totp.generate(step * totp.step)
+            .as_bytes()
+            .ct_eq(code.trim().as_bytes())
+            .into()
+    })
+}
+
+/// Generates recovery codes for an account, replacing any previous ones
+pub async fn generate_recovery_codes(
+    connection: &mut MySqlConnection,
+    token_hasher: &TokenHasher,
+    account_id: u64,
+) -> Result<Vec<String>, Error> {
+    let _ = sqlx::query("DELETE FROM recovery_codes WHERE account_id = ?")
+        .bind(account_id)
+        .execute(&mut *connection)
+        .await?;
+
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            rand::rng()
+                .sample_iter(&rand::distr::Alphanumeric)
+                .take(12)
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .collect()
+        })
+        .collect();
+
+    for code in &codes {
+        let _ = sqlx::query("INSERT INTO recovery_codes (account_id, code) VALUES (?, ?)")
+            .bind(account_id)
+            .bind(token_hasher.hash(code))
+            .execute(&mut *connection)
+            .await?;
+    }
+
+    Ok(codes)
+}
+
+/// Verifies a code of an account with enabled two-factor authentication,
+/// accepting either a one-time password that was not used before or a recovery code
+pub async fn verify(
+    connection: &mut MySqlConnection,
+    token_hasher: &TokenHasher,
+    account_id: u64,
+    code: &str,
+) -> Result<(), Error> {
+    let (email, secret, last_step): (String, String, Option<u64>) = sqlx::query_as(
+        "SELECT email, totp_secret, totp_last_step
+            FROM accounts
+            WHERE id = ? AND totp_enabled_at IS NOT NULL
+            LIMIT 1
+            FOR UPDATE",
+    )
+    .bind(account_id)
+    .fetch_optional(&mut *connection)
+    .await?
+    .ok_or(Error::NotEnrolled)?;
+
+    let secret = decrypt_secret(token_hasher, &secret)?;
+    if let Some(step) = check(&totp(&secret, email)?, code)
+        && last_step.is_none_or(|last_step| step > last_step)
+    {
+        let _ = sqlx::query("UPDATE accounts SET totp_last_step = ? WHERE id = ? LIMIT 1")
+            .bind(step)
+            .bind(account_id)
+            .execute(&mut *connection)
+            .await?;
+        return Ok(());
+    }
+
+    sqlx::query("DELETE FROM recovery_codes WHERE account_id = ? AND code = ? LIMIT 1")
+        .bind(account_id)
+        .bind(token_hasher.hash(code.trim()))
+        .execute(&mut *connection)
+        .await?
+        .rows_affected()
+        .ne(&0)
+        .then_some(())
+        .ok_or(Error::InvalidCode)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,40 @@
+use super::*;
+
+#[test]
+fn encrypts_secrets() {
+    let token_hasher = TokenHasher::new(b"test");
+    let secret = generate_secret();
+
+    let encrypted = encrypt_secret(&token_hasher, &secret);
+    assert!(!encrypted.contains(&secret));
+    assert_ne!(encrypted, encrypt_secret(&token_hasher, &secret));
+    assert_eq!(decrypt_secret(&token_hasher, &encrypted).unwrap(), secret);
+
+    // Another TOKEN_SECRET, or a tampered ciphertext, does not decrypt
+    assert!(matches!(
+        decrypt_secret(&TokenHasher::new(b"other"), &encrypted),
+        Err(Error::Decryption)
+    ));
+    let mut tampered = encrypted.into_bytes();
+    let last = tampered.last_mut().unwrap();
+    *last = if *last == b'0' { b'1' } else { b'0' };
+    assert!(matches!(
+        decrypt_secret(&token_hasher, &String::from_utf8(tampered).unwrap()),
+        Err(Error::Decryption)
+    ));
+    assert!(matches!(
+        decrypt_secret(&token_hasher, "00"),
+        Err(Error::Decryption)
+    ));
+}
+
+#[test]
+fn checks_codes_around_now() {
+    let totp = totp(&generate_secret(), "someone@example.org".to_owned()).unwrap();
+    let now = OffsetDateTime::now_utc().unix_timestamp().unsigned_abs();
+
+    let code = totp.generate(now);
+    assert_eq!(check(&totp, &format!(" {code} ")), Some(now / totp.step));
+    assert!(check(&totp, &totp.generate(now - 10 * totp.step)).is_none());
+    assert!(check(&totp, "").is_none());
+}
@@ -0,0 +1,88 @@
+use crate::{
+    ApiError, ApiResult, AppState,
+    audit::{self, Action},
+    permissions::{Require, require},
+    users::{
+        self, Roles,
+        auth::{signin::lockout, validate::Session},
+        email::EmailAddress,
+    },
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use axum_extra::extract::WithRejection as Rejectable;
+use serde_json::json;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(unlock))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[utoipa::path(
+    post,
+    path = "/users/{id}/unlock",
+    security(("session" = ["users.unlock"])),
+    description = "Forget failed signins of an account with a lower role, lifting its lockout",
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "User not found",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            description = "Insufficient permissions or limited to another co-op",
+        ),
+        (status = StatusCode::INTERNAL_SERVER_ERROR)
+    )
+)]
+pub async fn unlock(
+    State(AppState { pool, .. }): State<AppState>,
+    Require(session, _): Require<require::UsersUnlock, Session>,
+    Rejectable(Path(id), _): Rejectable<Path<u64>, ApiError>,
+) -> ApiResult<impl IntoResponse> {
+    let mut transaction = pool.begin().await.map_err(users::Error::Database)?;
+
+    let email: EmailAddress = sqlx::query_scalar(
+        "SELECT email FROM accounts WHERE id = ? AND deleted_at IS NULL LIMIT 1",
+    )
+    .bind(id)
+    .fetch_optional(&mut *transaction)
+    .await
+    .map_err(users::Error::Database)?
+    .ok_or(users::Error::NotFound)?;
+    let roles = Roles::load(&mut *transaction, id)
+        .await
+        .map_err(users::Error::Database)?;
+    users::require_manageable(&session, &roles)?;
+
+    let cleared = lockout::clear_failures(&mut *transaction, &email)
+        .await
+        .map_err(users::Error::Database)?;
+
+    audit::record(
+        &mut *transaction,
+        Some(session.account_id),
+        Action::Unlock,
+        Some(id),
+        json!({ "cleared": cleared }),
+    )
+    .await
+    .map_err(users::Error::Database)?;
+
+    transaction.commit().await.map_err(users::Error::Database)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}